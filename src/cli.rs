@@ -23,6 +23,22 @@ pub struct Cli {
     #[arg(long, default_value = "9876", global = true)]
     pub notify_port: u16,
 
+    /// Mount the workspace read-only inside the container
+    #[arg(long, global = true)]
+    pub read_only_workspace: bool,
+
+    /// Run the agent on an isolated copy of the workspace (git worktree or overlay)
+    #[arg(long, conflicts_with = "read_only_workspace")]
+    pub sandbox: bool,
+
+    /// Run a named session alongside the workspace's default container
+    #[arg(long, value_name = "NAME", global = true)]
+    pub session: Option<String>,
+
+    /// Let a named session use the workspace's home volume instead of its own
+    #[arg(long, requires = "session")]
+    pub share_home: bool,
+
     /// Publish container port(s) to the host (e.g. -p 8080:80)
     #[arg(short = 'p', long = "publish", value_name = "PORT")]
     pub ports: Vec<String>,
@@ -42,6 +58,50 @@ pub enum Command {
     /// Show notification daemon status
     ServerStatus,
 
+    /// Run the notification server as a systemd user service
+    Server {
+        #[command(subcommand)]
+        action: ServerCommand,
+    },
+
+    /// Show recent notification events from all containers
+    Events {
+        /// Keep printing new events as they arrive
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Number of past events to show
+        #[arg(short = 'n', long, default_value = "20")]
+        lines: usize,
+    },
+
+    /// Show past sessions with their duration, exit status and notifications
+    History {
+        /// Only sessions of this workspace (default with no value: cwd)
+        #[arg(long, num_args = 0..=1, default_missing_value = ".")]
+        workspace: Option<PathBuf>,
+
+        /// Only sessions started within this time, e.g. 12h, 7d
+        #[arg(long, value_name = "DURATION")]
+        since: Option<String>,
+
+        /// Number of sessions to list
+        #[arg(short = 'n', long, default_value = "20")]
+        lines: usize,
+    },
+
+    /// Show the notification server's log or a workspace container's output
+    Logs {
+        #[command(subcommand)]
+        target: LogsCommand,
+    },
+
+    /// Mute notifications per project and show the notification rules
+    Notify {
+        #[command(subcommand)]
+        action: NotifyCommand,
+    },
+
     /// Create ai-pod.Dockerfile in the workspace for editing
     Init {
         /// Workspace path (default: cwd)
@@ -49,14 +109,31 @@ pub enum Command {
         workdir: Option<PathBuf>,
     },
 
+    /// Attach to the running container of a workspace (or --session)
+    Attach {
+        /// Workspace path (default: cwd)
+        #[arg(long)]
+        workdir: Option<PathBuf>,
+    },
+
     /// List all claude containers
     List,
 
-    /// Remove the container for current/specified workspace
+    /// Remove the container for current/specified workspace (or --session)
     Clean {
         /// Workspace path (default: cwd)
         #[arg(long)]
         workdir: Option<PathBuf>,
+
+        /// Delete the home volume without snapshotting it first
+        #[arg(long)]
+        no_snapshot: bool,
+    },
+
+    /// Snapshot, restore, export or import the home volume
+    Volume {
+        #[command(subcommand)]
+        action: VolumeCommand,
     },
 
     /// Run a command in the container, overriding the default
@@ -69,3 +146,86 @@ pub enum Command {
         args: Vec<String>,
     },
 }
+
+#[derive(Subcommand)]
+pub enum ServerCommand {
+    /// Install and enable a socket-activated systemd user unit
+    Install,
+
+    /// Disable and remove the systemd user unit
+    Uninstall,
+}
+
+#[derive(Subcommand)]
+pub enum LogsCommand {
+    /// Show ~/.ai-pod/server.log
+    Server {
+        /// Keep printing new lines as they are written
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Number of past lines to show
+        #[arg(short = 'n', long, default_value = "50")]
+        lines: usize,
+    },
+
+    /// Show `podman logs` for the container of a workspace (or --session)
+    Container {
+        /// Workspace path (default: cwd)
+        #[arg(long)]
+        workdir: Option<PathBuf>,
+
+        /// Keep printing new output
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NotifyCommand {
+    /// Stop notifications from a project
+    Mute {
+        /// Project (workspace directory name; default: current directory)
+        project: Option<String>,
+
+        /// How long, e.g. 30m, 1h, 2d (default: until unmuted)
+        #[arg(long = "for", value_name = "DURATION")]
+        duration: Option<String>,
+    },
+
+    /// Resume notifications from a project
+    Unmute {
+        /// Project (workspace directory name; default: current directory)
+        project: Option<String>,
+    },
+
+    /// Show muted projects and the rules from config.toml
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum VolumeCommand {
+    /// Save a snapshot of the home volume under ~/.ai-pod/snapshots
+    Snapshot,
+
+    /// List snapshots of the home volume
+    List,
+
+    /// Replace the home volume with a snapshot
+    Restore {
+        /// Snapshot id from `volume list`, or `latest`
+        snapshot: String,
+    },
+
+    /// Write the home volume to a tar archive
+    Export {
+        /// Archive to write
+        path: PathBuf,
+    },
+
+    /// Replace the home volume with a tar archive
+    Import {
+        /// Archive to read
+        path: PathBuf,
+    },
+}
@@ -3,10 +3,36 @@ use std::path::PathBuf;
 
 pub struct AppConfig {
     pub config_dir: PathBuf,
+    /// User settings (`config.toml`), see [`crate::user_config`].
+    pub config_file: PathBuf,
     pub pid_file: PathBuf,
+    /// Held by the running notification server, so only one runs at a time.
+    pub server_lock: PathBuf,
+    /// Held while checking for and starting the server, so concurrent launches
+    /// don't both start one.
+    pub start_lock: PathBuf,
     pub log_file: PathBuf,
-    pub runtime_settings: PathBuf,
-    pub runtime_claude_md: PathBuf,
+    /// Addresses the running notification server listens on.
+    pub server_state: PathBuf,
+    /// Notification history kept by the server, shown by `ai-pod events` and the dashboard.
+    pub events_file: PathBuf,
+    /// Per-container tokens the notification server accepts on `/notify`.
+    pub token_dir: PathBuf,
+    /// Token host-side clients send to read events from the notification server.
+    pub server_token: PathBuf,
+    /// Projects muted with `ai-pod notify mute`, read by the notification server.
+    pub mutes_file: PathBuf,
+    /// Project policy files last confirmed per workspace, see [`crate::project::confirm_policy`].
+    pub trusted_policy: PathBuf,
+    /// Sessions started by `ai-pod`, shown by `ai-pod history`.
+    pub history_file: PathBuf,
+    pub egress_log: PathBuf,
+    pub sandbox_dir: PathBuf,
+    pub snapshot_dir: PathBuf,
+    /// Last host copies synced into each home volume, the base for three-way merges.
+    pub sync_dir: PathBuf,
+    /// Toolchains detected per image id, listed in the container's CLAUDE.md.
+    pub toolchain_cache: PathBuf,
     pub home_dir: PathBuf,
 }
 
@@ -17,9 +43,22 @@ impl AppConfig {
 
         Ok(Self {
             pid_file: config_dir.join("server.pid"),
+            server_lock: config_dir.join("server.lock"),
+            start_lock: config_dir.join("start.lock"),
             log_file: config_dir.join("server.log"),
-            runtime_settings: config_dir.join("runtime-settings.json"),
-            runtime_claude_md: config_dir.join("runtime-CLAUDE.md"),
+            server_state: config_dir.join("server.json"),
+            config_file: config_dir.join("config.toml"),
+            events_file: config_dir.join("events.jsonl"),
+            token_dir: config_dir.join("tokens"),
+            server_token: config_dir.join("server.token"),
+            mutes_file: config_dir.join("mutes.json"),
+            trusted_policy: config_dir.join("trusted-policy.json"),
+            history_file: config_dir.join("history.jsonl"),
+            egress_log: config_dir.join("egress.log"),
+            sandbox_dir: config_dir.join("sandboxes"),
+            snapshot_dir: config_dir.join("snapshots"),
+            sync_dir: config_dir.join("sync"),
+            toolchain_cache: config_dir.join("toolchains.json"),
             config_dir,
             home_dir,
         })
@@ -49,9 +88,22 @@ mod tests {
         let config_dir = home.join(".ai-pod");
         AppConfig {
             pid_file: config_dir.join("server.pid"),
+            server_lock: config_dir.join("server.lock"),
+            start_lock: config_dir.join("start.lock"),
             log_file: config_dir.join("server.log"),
-            runtime_settings: config_dir.join("runtime-settings.json"),
-            runtime_claude_md: config_dir.join("runtime-CLAUDE.md"),
+            server_state: config_dir.join("server.json"),
+            config_file: config_dir.join("config.toml"),
+            events_file: config_dir.join("events.jsonl"),
+            token_dir: config_dir.join("tokens"),
+            server_token: config_dir.join("server.token"),
+            mutes_file: config_dir.join("mutes.json"),
+            trusted_policy: config_dir.join("trusted-policy.json"),
+            history_file: config_dir.join("history.jsonl"),
+            egress_log: config_dir.join("egress.log"),
+            sandbox_dir: config_dir.join("sandboxes"),
+            snapshot_dir: config_dir.join("snapshots"),
+            sync_dir: config_dir.join("sync"),
+            toolchain_cache: config_dir.join("toolchains.json"),
             config_dir,
             home_dir: home,
         }
@@ -63,8 +115,21 @@ mod tests {
         let config = temp_config(&dir);
         assert!(config.pid_file.starts_with(&config.config_dir));
         assert!(config.log_file.starts_with(&config.config_dir));
-        assert!(config.runtime_settings.starts_with(&config.config_dir));
-        assert!(config.runtime_claude_md.starts_with(&config.config_dir));
+        assert!(config.config_file.starts_with(&config.config_dir));
+        assert!(config.server_state.starts_with(&config.config_dir));
+        assert!(config.events_file.starts_with(&config.config_dir));
+        assert!(config.token_dir.starts_with(&config.config_dir));
+        assert!(config.server_token.starts_with(&config.config_dir));
+        assert!(config.mutes_file.starts_with(&config.config_dir));
+        assert!(config.trusted_policy.starts_with(&config.config_dir));
+        assert!(config.history_file.starts_with(&config.config_dir));
+        assert!(config.server_lock.starts_with(&config.config_dir));
+        assert!(config.start_lock.starts_with(&config.config_dir));
+        assert!(config.egress_log.starts_with(&config.config_dir));
+        assert!(config.sandbox_dir.starts_with(&config.config_dir));
+        assert!(config.snapshot_dir.starts_with(&config.config_dir));
+        assert!(config.sync_dir.starts_with(&config.config_dir));
+        assert!(config.toolchain_cache.starts_with(&config.config_dir));
     }
 
     #[test]
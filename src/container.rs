@@ -1,21 +1,25 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::process::Command;
 
 use crate::config::AppConfig;
-
-const CONTAINER_CLAUDE_MD: &str = r#"# Container Environment
-You are running inside a Podman container. To reach services on the host machine,
-use `host.containers.internal` instead of `localhost`.
-
-For example: `curl http://host.containers.internal:3000`
-
-Working directory: /app
-"#;
-
-fn generate_container_name(workspace: &Path) -> String {
+use crate::egress::{EgressPolicy, EgressProxy};
+use crate::hardening::ResourceLimits;
+use crate::history;
+use crate::merge::{self, MergeReport};
+use crate::preamble::{self, EnvironmentContext, Toolchain};
+use crate::project;
+use crate::project::ProjectConfig;
+use crate::sandbox::Sandbox;
+use crate::server::auth::{self, TokenOwner};
+use crate::server::focus::Terminal;
+use crate::settings;
+use crate::volume::{self, SnapshotReason};
+
+pub fn generate_container_name(workspace: &Path) -> String {
     let workspace_str = workspace.to_string_lossy();
     let hash = Sha256::digest(workspace_str.as_bytes());
     let short_hash = hex::encode(&hash[..6]);
@@ -29,6 +33,64 @@ fn generate_volume_name(workspace: &Path) -> String {
     format!("claude-{}-home", short_hash)
 }
 
+/// Label recording the workspace a container belongs to, used to group `ai-pod list`.
+pub const WORKSPACE_LABEL: &str = "ai-pod.workspace";
+/// Label recording the session name; the unnamed session is listed as `default`.
+pub const SESSION_LABEL: &str = "ai-pod.session";
+pub const DEFAULT_SESSION: &str = "default";
+/// Session names that would be confused with the default session or its init container.
+const RESERVED_SESSIONS: &[&str] = &[DEFAULT_SESSION, "init"];
+
+/// Session names end up in container and volume names, so they are restricted to
+/// characters podman accepts there.
+pub fn validate_session_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "Invalid session name '{}': use up to 32 lowercase letters, digits, '-' or '_'",
+            name
+        );
+    }
+    if RESERVED_SESSIONS.contains(&name) {
+        anyhow::bail!("Session name '{}' is reserved", name);
+    }
+    Ok(())
+}
+
+/// Container name for a named session of `workspace`; `None` is the default session.
+pub fn session_container_name(workspace: &Path, session: Option<&str>) -> String {
+    match session {
+        Some(session) => format!("{}-{}", generate_container_name(workspace), session),
+        None => generate_container_name(workspace),
+    }
+}
+
+/// Name of a short-lived helper container of `container`, such as the one used to sync
+/// its home volume. Session names can't contain '.', so it never names a session's
+/// container, which `podman rm --force` on the helper would otherwise take down.
+fn helper_container_name(container: &str, purpose: &str) -> String {
+    format!("{}.{}", container, purpose)
+}
+
+/// Home volume for a named session. Sessions get their own volume unless they share
+/// the workspace's.
+fn session_volume_name(workspace: &Path, session: Option<&str>) -> String {
+    match session {
+        Some(session) => format!("{}-{}-home", generate_container_name(workspace), session),
+        None => generate_volume_name(workspace),
+    }
+}
+
+/// Home volume used by `session`, honouring `--share-home`.
+pub fn home_volume_name(workspace: &Path, session: Option<&str>, share_home: bool) -> String {
+    session_volume_name(workspace, session.filter(|_| !share_home))
+}
+
 fn container_exists(name: &str) -> Result<bool> {
     let output = Command::new("podman")
         .args([
@@ -45,7 +107,7 @@ fn container_exists(name: &str) -> Result<bool> {
     Ok(!output.stdout.is_empty())
 }
 
-fn container_is_running(name: &str) -> Result<bool> {
+pub fn container_is_running(name: &str) -> Result<bool> {
     let output = Command::new("podman")
         .args([
             "ps",
@@ -60,7 +122,7 @@ fn container_is_running(name: &str) -> Result<bool> {
     Ok(!output.stdout.is_empty())
 }
 
-fn volume_exists(name: &str) -> Result<bool> {
+pub fn volume_exists(name: &str) -> Result<bool> {
     let status = Command::new("podman")
         .args(["volume", "exists", name])
         .status()
@@ -68,8 +130,27 @@ fn volume_exists(name: &str) -> Result<bool> {
     Ok(status.success())
 }
 
-fn generate_runtime_claude_md(config: &AppConfig) -> Result<()> {
-    let mut content = CONTAINER_CLAUDE_MD.to_string();
+/// Writes the runtime CLAUDE.md into `runtime_dir`: the generated environment preamble,
+/// the project's `ai-pod.CLAUDE.md` fragment and the host's `~/.claude/CLAUDE.md`, in
+/// that order.
+fn generate_runtime_claude_md(
+    config: &AppConfig,
+    opts: &LaunchOptions,
+    toolchains: &[Toolchain],
+    runtime_dir: &Path,
+) -> Result<()> {
+    let mut content = preamble::render(&EnvironmentContext {
+        project: opts.project,
+        ports: opts.ports,
+        read_only_workspace: opts.read_only_workspace,
+        sandboxed: opts.sandbox.is_some(),
+        toolchains,
+    });
+
+    if let Some(fragment) = project::load_claude_md_fragment(opts.workspace)? {
+        content.push('\n');
+        content.push_str(&fragment);
+    }
 
     let host_claude_md = config.claude_md_path();
     if host_claude_md.exists() {
@@ -79,60 +160,464 @@ fn generate_runtime_claude_md(config: &AppConfig) -> Result<()> {
         content.push_str(&existing);
     }
 
-    std::fs::write(&config.runtime_claude_md, content)
+    std::fs::write(runtime_dir.join("CLAUDE.md"), content)
         .context("Failed to write runtime CLAUDE.md")?;
 
     Ok(())
 }
 
-fn project_name(workspace: &Path) -> String {
+pub fn project_name(workspace: &Path) -> String {
     workspace
         .file_name()
         .map(|n| n.to_string_lossy().into_owned())
         .unwrap_or_else(|| "Claude Code".to_string())
 }
 
-fn generate_runtime_settings(config: &AppConfig) -> Result<()> {
-    let mut settings: serde_json::Value = if config.claude_settings_path().exists() {
-        let raw = std::fs::read_to_string(config.claude_settings_path())
-            .context("Failed to read settings.json")?;
-        serde_json::from_str(&raw).unwrap_or_else(|_| serde_json::json!({}))
-    } else {
-        serde_json::json!({})
+/// `podman run` arguments controlling how the container reaches the host.
+///
+/// Without a `host_access` list the host gateway is mapped as-is. With one, the
+/// container gets a pasta network that doesn't map the gateway at all; only the listed
+/// ports (plus the notification server and egress proxy) are forwarded from the
+/// container's loopback to the host's, and `host.containers.internal` points at loopback.
+/// The egress proxy only listens on the host's loopback, so its port is always forwarded.
+fn host_access_args(
+    project: &ProjectConfig,
+    notify_port: u16,
+    egress: Option<&EgressProxy>,
+) -> Vec<String> {
+    let services: &[project::HostService] = match (&project.host_access, egress) {
+        (Some(services), _) => services,
+        // A restricted network has no route to the host gateway; only forwarded ports
+        // can be reached.
+        (None, Some(_)) => &[],
+        (None, None) => return vec!["--add-host=host.containers.internal:host-gateway".into()],
     };
 
-    let hook_command = "curl -sf -X POST \"$NOTIFY_URL\" || true".to_string();
+    let mut ports: Vec<u16> = services.iter().map(|s| s.port()).collect();
+    ports.push(notify_port);
+    ports.extend(egress.map(|e| e.port));
+    ports.sort_unstable();
+    ports.dedup();
+
+    let mut network = "pasta:--no-map-gw".to_string();
+    for port in ports {
+        network.push_str(&format!(",-T,{}", port));
+    }
+    vec![
+        format!("--network={}", network),
+        "--add-host=host.containers.internal:127.0.0.1".into(),
+    ]
+}
 
-    let stop_hook = serde_json::json!([{
-        "matcher": "*",
-        "hooks": [{
-            "type": "command",
-            "command": hook_command
-        }]
-    }]);
+/// Routes are removed from the container's network namespace with `ip` run there by
+/// `podman unshare`, which owns the namespace in rootless podman. Loopback routes live in
+/// the `local` table and stay, so ports pasta forwards from the container's loopback
+/// still work.
+fn isolate_network_commands(pid: &str) -> Vec<Vec<String>> {
+    ["-4", "-6"]
+        .iter()
+        .map(|family| {
+            let ip = ["ip", family, "route", "flush", "table", "main"];
+            ["unshare", "nsenter", "-t", pid, "-n"]
+                .iter()
+                .chain(&ip)
+                .map(|arg| arg.to_string())
+                .collect()
+        })
+        .collect()
+}
 
-    let obj = settings
-        .as_object_mut()
-        .context("settings.json is not an object")?;
+/// Leaves the created, not yet started container `name` without routes, so the only
+/// connections it can make are to the egress proxy and the host ports forwarded to it.
+fn isolate_network(name: &str) -> Result<()> {
+    let output = Command::new("podman")
+        .args(["inspect", "--format", "{{.State.Pid}}", name])
+        .output()
+        .context("Failed to inspect container")?;
+    let pid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || pid.is_empty() || pid == "0" {
+        anyhow::bail!("Container {} has no process to isolate", name);
+    }
+    for (n, args) in isolate_network_commands(&pid).iter().enumerate() {
+        let status = Command::new("podman")
+            .args(args)
+            .status()
+            .context("Failed to run ip in the container's network namespace")?;
+        // Without IPv6 in the namespace there are no IPv6 routes to remove.
+        if !status.success() && n == 0 {
+            anyhow::bail!("Failed to remove the container's routes (needs nsenter and ip)");
+        }
+    }
+    Ok(())
+}
 
-    let hooks = obj.entry("hooks").or_insert_with(|| serde_json::json!({}));
-    let hooks_obj = hooks.as_object_mut().context("hooks is not an object")?;
-    hooks_obj.insert("Stop".to_string(), stop_hook);
+/// Runs `podman run` with `run_args` and returns its exit code. A container under a
+/// network policy is created and initialized first, and only started once it has been
+/// cut off from everything but the egress proxy; it is removed if that fails.
+fn podman_run(
+    run_args: &[String],
+    name: &str,
+    egress: Option<&EgressProxy>,
+) -> Result<Option<i32>> {
+    let status = if egress.is_none() {
+        Command::new("podman")
+            .arg("run")
+            .args(run_args)
+            .status()
+            .context("Failed to run container")?
+    } else {
+        let created = Command::new("podman")
+            .arg("create")
+            .args(run_args)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .context("Failed to create container")?;
+        if !created.success() {
+            anyhow::bail!("Failed to create container {}", name);
+        }
+        let isolated = Command::new("podman")
+            .args(["init", name])
+            .stdout(std::process::Stdio::null())
+            .status()
+            .context("Failed to initialize container")
+            .and_then(|status| match status.success() {
+                true => isolate_network(name),
+                false => Err(anyhow::anyhow!("Failed to initialize container {}", name)),
+            });
+        if let Err(e) = isolated {
+            let _ = Command::new("podman")
+                .args(["rm", "--force", name])
+                .stdout(std::process::Stdio::null())
+                .status();
+            return Err(e.context("Could not enforce the network policy"));
+        }
+        Command::new("podman")
+            .args(["start", "--attach", "--interactive", name])
+            .status()
+            .context("Failed to start container")?
+    };
+    Ok(status.code())
+}
+
+/// Starts the host-side egress proxy when the project restricts network access.
+fn start_egress_proxy(
+    config: &AppConfig,
+    workspace: &Path,
+    project: &ProjectConfig,
+) -> Result<Option<EgressProxy>> {
+    let Some(policy) = EgressPolicy::from_config(&project.network)? else {
+        return Ok(None);
+    };
+    let proxy = EgressProxy::start(policy, &project_name(workspace), &config.egress_log)?;
+    println!(
+        "{} {} (denied requests are logged to {})",
+        "Network policy:".blue(),
+        project.network.mode.as_str(),
+        config.egress_log.display()
+    );
+    Ok(Some(proxy))
+}
+
+/// Writes the runtime settings.json into `runtime_dir`: the host's settings with ai-pod's
+/// hooks and the project's `[claude]` additions merged in. Returns false if the host
+/// settings could not be parsed, in which case only the container defaults are written.
+fn generate_runtime_settings(
+    config: &AppConfig,
+    project: &ProjectConfig,
+    runtime_dir: &Path,
+) -> Result<bool> {
+    let path = config.claude_settings_path();
+    let host = if path.exists() {
+        let raw = std::fs::read_to_string(&path).context("Failed to read settings.json")?;
+        match settings::parse_host_settings(&raw) {
+            Ok(host) => Some(host),
+            Err(e) => {
+                eprintln!(
+                    "{} {} {e:#}; using container defaults only",
+                    "Warning:".yellow(),
+                    path.display()
+                );
+                None
+            }
+        }
+    } else {
+        Some(serde_json::json!({}))
+    };
+    let host_ok = host.is_some();
+
+    let host = host.unwrap_or_else(|| serde_json::json!({}));
+    let (settings, warnings) = settings::runtime_settings(host, &project.claude);
+    for warning in warnings {
+        eprintln!("{} settings.json: {}", "Warning:".yellow(), warning);
+    }
 
     let output = serde_json::to_string_pretty(&settings)?;
-    std::fs::write(&config.runtime_settings, output).context("Failed to write runtime settings")?;
+    std::fs::write(runtime_dir.join("settings.json"), output)
+        .context("Failed to write runtime settings")?;
+
+    Ok(host_ok)
+}
 
+/// Runtime config kept in sync between host and home volume: file name (also used for
+/// the runtime copy and the sync record) and its path inside the container. The runtime
+/// copies are generated into a temporary directory per launch, so concurrent launches
+/// don't overwrite each other's.
+const SYNCED_FILES: &[(&str, &str)] = &[
+    ("settings.json", "/home/claude/.claude/settings.json"),
+    ("CLAUDE.md", "/home/claude/.claude/CLAUDE.md"),
+];
+
+const CLAUDE_JSON_PATH: &str = "/home/claude/.claude.json";
+
+/// Stores the runtime config just written to `volume_name` as the base for the next merge.
+fn record_synced(
+    config: &AppConfig,
+    volume_name: &str,
+    runtime_dir: &Path,
+    skip: &[&str],
+) -> Result<()> {
+    let dir = config.sync_dir.join(volume_name);
+    std::fs::create_dir_all(&dir).context("Failed to create sync directory")?;
+    for (name, _) in SYNCED_FILES.iter().filter(|(name, _)| !skip.contains(name)) {
+        std::fs::copy(runtime_dir.join(name), dir.join(name))
+            .with_context(|| format!("Failed to record synced {}", name))?;
+    }
     Ok(())
 }
 
+/// The merge base for `name`: the copy recorded at the last sync. Volumes created before
+/// syncs were recorded hold the copy ai-pod last wrote, so that is the base the first time.
+fn sync_base(record_dir: &Path, name: &str, ours: Option<&str>) -> Option<String> {
+    std::fs::read_to_string(record_dir.join(name))
+        .ok()
+        .or_else(|| ours.map(str::to_string))
+}
+
+/// Three-way merges one synced file. Returns the new volume contents if they changed.
+fn merge_synced_file(
+    name: &str,
+    base: Option<&str>,
+    ours: Option<&str>,
+    theirs: &str,
+) -> Result<(Option<String>, MergeReport)> {
+    let Some(ours) = ours else {
+        let report = MergeReport {
+            updated: vec!["(new file)".into()],
+            ..Default::default()
+        };
+        return Ok((Some(theirs.to_string()), report));
+    };
+
+    if !name.ends_with(".json") {
+        let (merged, report) = merge::merge_text(base, ours, theirs);
+        return Ok(((merged != ours).then_some(merged), report));
+    }
+
+    let theirs: serde_json::Value = serde_json::from_str(theirs)?;
+    let Ok(ours_value) = serde_json::from_str::<serde_json::Value>(ours) else {
+        let report = MergeReport {
+            conflicts: vec!["(container copy is not valid JSON)".into()],
+            ..Default::default()
+        };
+        return Ok((None, report));
+    };
+    let base = base.and_then(|b| serde_json::from_str::<serde_json::Value>(b).ok());
+    let (merged, report) = merge::merge_json(base.as_ref(), &ours_value, &theirs);
+    if merged == ours_value {
+        return Ok((None, report));
+    }
+    Ok((Some(serde_json::to_string_pretty(&merged)?), report))
+}
+
+fn print_merge_report(name: &str, report: &MergeReport) {
+    if !report.updated.is_empty() {
+        println!(
+            "{} {} ({})",
+            "Synced from host:".green(),
+            name,
+            report.updated.join(", ")
+        );
+    }
+    if !report.conflicts.is_empty() {
+        println!(
+            "{} {} ({})",
+            "Kept container edits:".yellow(),
+            name,
+            report.conflicts.join(", ")
+        );
+    }
+}
+
+/// Copies `path` out of `container` into `local`. Returns false if it doesn't exist.
+fn copy_from_container(container: &str, path: &str, local: &Path) -> Result<bool> {
+    Ok(Command::new("podman")
+        .args([
+            "cp",
+            &format!("{}:{}", container, path),
+            &local.to_string_lossy(),
+        ])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .context("Failed to run podman cp")?
+        .success())
+}
+
+fn copy_into_container(local: &Path, container: &str, path: &str) -> Result<()> {
+    let status = Command::new("podman")
+        .args([
+            "cp",
+            &local.to_string_lossy(),
+            &format!("{}:{}", container, path),
+        ])
+        .status()
+        .context("Failed to run podman cp")?;
+    if !status.success() {
+        anyhow::bail!("Failed to copy {} into the home volume", path);
+    }
+    Ok(())
+}
+
+/// Adds the project's MCP servers to `~/.claude.json` in the volume mounted by `helper`.
+fn sync_mcp_servers(helper: &str, project: &ProjectConfig, tmp: &Path) -> Result<()> {
+    if project.claude.mcp_servers.is_empty() {
+        return Ok(());
+    }
+    let local = tmp.join(".claude.json");
+    let mut claude_json = if copy_from_container(helper, CLAUDE_JSON_PATH, &local)? {
+        let raw = std::fs::read_to_string(&local)?;
+        match serde_json::from_str(&raw) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!(
+                    "{} .claude.json in the home volume is not valid JSON ({e}); MCP servers not added",
+                    "Warning:".yellow()
+                );
+                return Ok(());
+            }
+        }
+    } else {
+        serde_json::json!({})
+    };
+
+    let mut warnings = Vec::new();
+    let changed = settings::merge_mcp_servers(&mut claude_json, &project.claude, &mut warnings);
+    for warning in warnings {
+        eprintln!("{} {}", "Warning:".yellow(), warning);
+    }
+    if changed.is_empty() {
+        return Ok(());
+    }
+    std::fs::write(&local, serde_json::to_string_pretty(&claude_json)?)?;
+    copy_into_container(&local, helper, CLAUDE_JSON_PATH)?;
+    println!("{} {}", "MCP servers:".green(), changed.join(", "));
+    Ok(())
+}
+
+fn resync_files(
+    config: &AppConfig,
+    volume_name: &str,
+    helper: &str,
+    project: &ProjectConfig,
+    runtime_dir: &Path,
+    skip: &[&str],
+) -> Result<()> {
+    let tmp = tempfile::TempDir::new().context("Failed to create temp dir")?;
+    let record_dir = config.sync_dir.join(volume_name);
+
+    for (name, container_path) in SYNCED_FILES {
+        if skip.contains(name) {
+            println!(
+                "{} {} (fix the host copy to resume syncing)",
+                "Not synced:".yellow(),
+                name
+            );
+            continue;
+        }
+        let theirs = std::fs::read_to_string(runtime_dir.join(name))
+            .with_context(|| format!("Failed to read runtime {}", name))?;
+        let local = tmp.path().join(name);
+        let ours = if copy_from_container(helper, container_path, &local)? {
+            std::fs::read_to_string(&local).ok()
+        } else {
+            None
+        };
+        let base = sync_base(&record_dir, name, ours.as_deref());
+
+        let (update, report) = merge_synced_file(name, base.as_deref(), ours.as_deref(), &theirs)
+            .with_context(|| format!("Failed to merge {}", name))?;
+        if let Some(content) = update {
+            std::fs::write(&local, content)?;
+            copy_into_container(&local, helper, container_path)?;
+        }
+        print_merge_report(name, &report);
+    }
+
+    sync_mcp_servers(helper, project, tmp.path())?;
+    record_synced(config, volume_name, runtime_dir, skip)
+}
+
+/// Brings host changes to settings.json and CLAUDE.md into an existing home volume,
+/// three-way merging against the copy recorded at the last sync so that edits made
+/// inside the container survive.
+fn resync_home_volume(
+    config: &AppConfig,
+    volume_name: &str,
+    container_name: &str,
+    opts: &LaunchOptions,
+) -> Result<()> {
+    let image = opts.image;
+    let toolchains = preamble::detect_toolchains(config, image);
+    let runtime = tempfile::TempDir::new().context("Failed to create temp dir")?;
+    generate_runtime_claude_md(config, opts, &toolchains, runtime.path())?;
+    let skip: &[&str] = if generate_runtime_settings(config, opts.project, runtime.path())? {
+        &[]
+    } else {
+        &["settings.json"]
+    };
+
+    // A stopped helper container gives `podman cp` access to the volume.
+    let helper = helper_container_name(container_name, "sync");
+    let _ = Command::new("podman")
+        .args(["rm", "--force", &helper])
+        .output();
+    let status = Command::new("podman")
+        .args([
+            "create",
+            "--name",
+            &helper,
+            "-v",
+            &format!("{}:/home/claude", volume_name),
+            image,
+        ])
+        .stdout(std::process::Stdio::null())
+        .status()
+        .context("Failed to create sync container")?;
+    if !status.success() {
+        anyhow::bail!("Failed to create sync container");
+    }
+
+    let result = resync_files(
+        config,
+        volume_name,
+        &helper,
+        opts.project,
+        runtime.path(),
+        skip,
+    );
+    let _ = Command::new("podman").args(["rm", &helper]).output();
+    result
+}
+
 /// Initialize a named home volume for the first time.
-/// Creates skeleton dirs, copies host ~/.claude.json and ~/.claude/, and injects runtime config.
+/// Creates skeleton dirs, syncs the allowlisted parts of host ~/.claude.json and ~/.claude/,
+/// and injects runtime config.
 fn init_home_volume(
     config: &AppConfig,
     volume_name: &str,
     container_name: &str,
-    image: &str,
+    opts: &LaunchOptions,
 ) -> Result<()> {
+    let image = opts.image;
     println!(
         "{} {}",
         "Initialising home volume:".blue().bold(),
@@ -171,7 +656,7 @@ fn init_home_volume(
     }
 
     // 3. Create a stopped container for cp operations
-    let init_container = format!("{}-init", container_name);
+    let init_container = helper_container_name(container_name, "init");
     let status = Command::new("podman")
         .args([
             "create",
@@ -187,51 +672,39 @@ fn init_home_volume(
         anyhow::bail!("Failed to create init container");
     }
 
-    // 4. Copy ~/.claude.json (soft error)
-    let claude_json = config.home_dir.join(".claude.json");
-    if claude_json.exists() {
-        let _ = Command::new("podman")
-            .args([
-                "cp",
-                &claude_json.to_string_lossy(),
-                &format!("{}:/home/claude/", init_container),
-            ])
-            .status();
+    // 4. Copy the allowlisted parts of ~/.claude and ~/.claude.json (soft error)
+    if let Err(e) = crate::home::sync_claude_home(config, &init_container) {
+        eprintln!("{} {e:#}", "Failed to sync ~/.claude:".yellow());
     }
 
-    // 5. Copy ~/.claude/ (soft error)
-    let claude_dir = config.home_dir.join(".claude");
-    if claude_dir.exists() {
+    // 5. Generate and copy runtime config
+    let toolchains = preamble::detect_toolchains(config, image);
+    let runtime = tempfile::TempDir::new().context("Failed to create temp dir")?;
+    generate_runtime_claude_md(config, opts, &toolchains, runtime.path())?;
+    // As in `resync_home_volume`: unreadable host settings aren't copied or recorded,
+    // so the first sync after they are fixed brings them in.
+    let skip: &[&str] = if generate_runtime_settings(config, opts.project, runtime.path())? {
+        &[]
+    } else {
+        &["settings.json"]
+    };
+
+    for (name, container_path) in SYNCED_FILES.iter().filter(|(name, _)| !skip.contains(name)) {
         let _ = Command::new("podman")
             .args([
                 "cp",
-                &format!("{}/.", claude_dir.to_string_lossy()),
-                &format!("{}:/home/claude/.claude/", init_container),
+                &runtime.path().join(name).to_string_lossy(),
+                &format!("{}:{}", init_container, container_path),
             ])
             .status();
     }
+    record_synced(config, volume_name, runtime.path(), skip)?;
+    let tmp = tempfile::TempDir::new().context("Failed to create temp dir")?;
+    if let Err(e) = sync_mcp_servers(&init_container, opts.project, tmp.path()) {
+        eprintln!("{} {e:#}", "Failed to add MCP servers:".yellow());
+    }
 
-    // 6. Generate and copy runtime config
-    generate_runtime_claude_md(config)?;
-    generate_runtime_settings(config)?;
-
-    let _ = Command::new("podman")
-        .args([
-            "cp",
-            &config.runtime_claude_md.to_string_lossy(),
-            &format!("{}:/home/claude/.claude/CLAUDE.md", init_container),
-        ])
-        .status();
-
-    let _ = Command::new("podman")
-        .args([
-            "cp",
-            &config.runtime_settings.to_string_lossy(),
-            &format!("{}:/home/claude/.claude/settings.json", init_container),
-        ])
-        .status();
-
-    // 7. Remove init container
+    // 6. Remove init container
     let _ = Command::new("podman")
         .args(["rm", &init_container])
         .status();
@@ -241,17 +714,307 @@ fn init_home_volume(
     Ok(())
 }
 
-pub fn launch_container(
-    config: &AppConfig,
+/// Settings shared by `launch_container` and `run_in_container`.
+pub struct LaunchOptions<'a> {
+    pub workspace: &'a Path,
+    pub image: &'a str,
+    pub notify_port: u16,
+    pub ports: &'a [String],
+    pub project: &'a ProjectConfig,
+    /// Mount `/app` read-only.
+    pub read_only_workspace: bool,
+    /// Mount this sandbox as `/app` instead of the live workspace.
+    pub sandbox: Option<&'a Sandbox>,
+    /// Named session to run; `None` is the workspace's default session.
+    pub session: Option<&'a str>,
+    /// Use the workspace's home volume instead of a per-session one.
+    pub share_home: bool,
+}
+
+impl LaunchOptions<'_> {
+    /// Session the container runs as. A sandbox without an explicit name runs as its own
+    /// session so it does not collide with the default container.
+    fn session_name(&self) -> Option<&str> {
+        self.session.or(self.sandbox.map(|s| s.session.as_str()))
+    }
+
+    pub fn container_name(&self) -> String {
+        session_container_name(self.workspace, self.session_name())
+    }
+
+    /// Unnamed sandboxes keep using the workspace's home volume, as named sessions do
+    /// with `--share-home`.
+    fn volume_name(&self) -> String {
+        home_volume_name(self.workspace, self.session, self.share_home)
+    }
+
+    fn labels(&self) -> Vec<String> {
+        vec![
+            format!(
+                "--label={}={}",
+                WORKSPACE_LABEL,
+                self.workspace.to_string_lossy()
+            ),
+            format!(
+                "--label={}={}",
+                SESSION_LABEL,
+                self.session_name().unwrap_or(DEFAULT_SESSION)
+            ),
+        ]
+    }
+}
+
+/// Mount arguments for the workspace (or the sandbox standing in for it): `/app`
+/// itself plus read-only bind mounts for each protected path, so the agent can't
+/// modify them even when `/app` is writable. Protected paths that don't exist yet get
+/// an empty read-only directory, so the agent can't create them either.
+fn workspace_mount_args(
     workspace: &Path,
-    port: u16,
-    rebuild: bool,
-    image: &str,
-    ports: &[String],
-) -> Result<()> {
-    let container_name = generate_container_name(workspace);
-    let volume_name = generate_volume_name(workspace);
-    let workspace_str = workspace.to_string_lossy();
+    read_only: bool,
+    protected_paths: &[String],
+    sandbox: Option<&Sandbox>,
+) -> Result<Vec<String>> {
+    if read_only {
+        let workspace_str = workspace.to_string_lossy();
+        return Ok(vec!["-v".into(), format!("{}:/app:ro,Z", workspace_str)]);
+    }
+
+    let (root, mut args) = match sandbox {
+        Some(sandbox) => (sandbox.root(), sandbox.mount_args()),
+        None => (
+            workspace,
+            vec!["-v".into(), format!("{}:/app:Z", workspace.to_string_lossy())],
+        ),
+    };
+    let canonical_root = root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", root.display()))?;
+    for raw in protected_paths {
+        let relative = Path::new(raw.trim_end_matches('/'));
+        let escapes = relative.components().any(|c| {
+            !matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir)
+        });
+        if escapes || relative.as_os_str().is_empty() {
+            anyhow::bail!(
+                "Protected path '{}' must be relative to the workspace and must not contain '..'",
+                raw
+            );
+        }
+
+        if sandbox.is_some_and(Sandbox::protects_git_dir) && relative.starts_with(".git") {
+            continue;
+        }
+        let source = root.join(relative);
+        if source.symlink_metadata().is_err() {
+            args.push(format!("--tmpfs=/app/{}:ro", relative.to_string_lossy()));
+            continue;
+        }
+        // Mount what the path resolves to, where it resolves to, so a symlink can
+        // neither expose a host directory nor leave its target writable.
+        let resolved = source
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve protected path '{}'", raw))?;
+        let Ok(inside) = resolved.strip_prefix(&canonical_root) else {
+            anyhow::bail!(
+                "Protected path '{}' resolves to {}, outside the workspace",
+                raw,
+                resolved.display()
+            );
+        };
+        args.push("-v".into());
+        args.push(format!(
+            "{}:{}:ro",
+            resolved.to_string_lossy(),
+            Path::new("/app").join(inside).to_string_lossy()
+        ));
+    }
+    // The agent must not be able to relax its own policy for the next launch.
+    for name in project::POLICY_FILES {
+        let source = root.join(name);
+        if source.symlink_metadata().is_ok_and(|m| m.is_file()) {
+            args.push("-v".into());
+            args.push(format!("{}:/app/{}:ro", source.to_string_lossy(), name));
+        }
+    }
+    Ok(args)
+}
+
+/// Builds the `podman run` arguments shared by interactive sessions and one-off
+/// commands, up to but excluding the image, for a container named `container`. The
+/// returned proxy must be kept alive until the container exits.
+fn session_run_args(
+    config: &AppConfig,
+    opts: &LaunchOptions,
+    container: &str,
+    volume_name: &str,
+    history_id: &str,
+) -> Result<(Vec<String>, Option<EgressProxy>)> {
+    let limits = ResourceLimits::from_config(&opts.project.hardening)?;
+    println!(
+        "{} {} ({})",
+        "Hardening:".blue(),
+        limits.profile.as_str(),
+        limits.summary()
+    );
+    if opts.read_only_workspace {
+        println!("{}", "Workspace is mounted read-only.".blue());
+    }
+
+    let egress = start_egress_proxy(config, opts.workspace, opts.project)?;
+    let token = auth::issue(
+        &config.token_dir,
+        TokenOwner {
+            project: project_name(opts.workspace),
+            container: container.to_string(),
+            workspace: opts.workspace.to_string_lossy().into_owned(),
+            session: opts.session_name().unwrap_or(DEFAULT_SESSION).to_string(),
+            started: Some(chrono::Utc::now()),
+            terminal: Terminal::detect(),
+            history_id: Some(history_id.to_string()),
+        },
+    )?;
+    let mut args: Vec<String> = vec!["-v".into(), format!("{}:/home/claude:z", volume_name)];
+    args.extend(workspace_mount_args(
+        opts.workspace,
+        opts.read_only_workspace,
+        &opts.project.protected_paths,
+        opts.sandbox,
+    )?);
+    args.extend([
+        "-e".into(),
+        "HOST_GATEWAY=host.containers.internal".into(),
+        "-e".into(),
+        format!(
+            "NOTIFY_URL=http://host.containers.internal:{}/notify",
+            opts.notify_port
+        ),
+        "-e".into(),
+        format!("NOTIFY_TOKEN={}", token),
+    ]);
+    for p in opts.ports {
+        args.push("-p".into());
+        args.push(p.clone());
+    }
+    args.extend(host_access_args(opts.project, opts.notify_port, egress.as_ref()));
+    if let Some(egress) = &egress {
+        let net_admin = |cap: &String| {
+            cap.trim_start_matches("CAP_")
+                .eq_ignore_ascii_case("NET_ADMIN")
+        };
+        if limits.cap_add.iter().any(net_admin) {
+            anyhow::bail!(
+                "cap_add = [\"NET_ADMIN\"] would let the container restore its routes; \
+                 it can't be combined with network mode \"{}\"",
+                opts.project.network.mode.as_str()
+            );
+        }
+        args.extend(egress.container_args());
+    }
+    args.extend(limits.podman_args());
+    args.extend(opts.labels());
+    Ok((args, egress))
+}
+
+/// Called once `podman run` has returned. A container that still runs was detached
+/// from and keeps its session token; if it depends on `egress`, this waits for it to
+/// stop so its network keeps working. The token is revoked once the container is gone.
+fn finish_run(config: &AppConfig, container: &str, egress: Option<EgressProxy>) -> Result<()> {
+    if container_is_running(container)? {
+        if egress.is_none() {
+            return Ok(());
+        }
+        println!(
+            "{}",
+            "Detached. The network proxy keeps running until the container stops; \
+             press Ctrl+C to stop it (the container then loses network access)."
+                .yellow()
+        );
+        let _ = Command::new("podman")
+            .args(["wait", container])
+            .stdout(std::process::Stdio::null())
+            .status();
+    }
+    auth::revoke(&config.token_dir, container);
+    Ok(())
+}
+
+/// A failed resync leaves the volume as it was; the session can still start.
+fn resync_or_warn(config: &AppConfig, opts: &LaunchOptions, volume: &str, container: &str) {
+    if let Err(e) = resync_home_volume(config, volume, container, opts) {
+        eprintln!("{} {e:#}", "Failed to sync host config:".yellow());
+    }
+}
+
+/// Returns the exit code of `podman attach`.
+fn podman_attach(container_name: &str) -> Result<Option<i32>> {
+    let status = Command::new("podman")
+        .args(["attach", container_name])
+        .stdin(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit())
+        .status()
+        .context("Failed to attach to container")?;
+    // Non-zero exits (detach=0, ctrl+c=130) are intentionally ignored
+    Ok(status.code())
+}
+
+/// Attaches to the running container of a workspace session, without starting one.
+pub fn attach_container(workspace: &Path, session: Option<&str>) -> Result<()> {
+    let container_name = session_container_name(workspace, session);
+    if !container_is_running(&container_name)? {
+        let start = match session {
+            Some(s) => format!("ai-pod --session {}", s),
+            None => "ai-pod".to_string(),
+        };
+        anyhow::bail!(
+            "No running container for {} ({}).\nRun `{}` in the workspace to start one.",
+            workspace.display(),
+            container_name,
+            start
+        );
+    }
+    println!(
+        "{} {}",
+        "Attaching to running container:".green(),
+        container_name
+    );
+    podman_attach(&container_name)?;
+    Ok(())
+}
+
+/// `ai-pod logs container`. Containers are removed when their session ends, and their
+/// output with them.
+pub fn container_logs(workspace: &Path, session: Option<&str>, follow: bool) -> Result<()> {
+    let container_name = session_container_name(workspace, session);
+    if !container_exists(&container_name)? {
+        anyhow::bail!(
+            "No container for {} ({}); its output is removed when the session ends.",
+            workspace.display(),
+            container_name
+        );
+    }
+    let mut cmd = Command::new("podman");
+    cmd.arg("logs");
+    if follow {
+        cmd.arg("--follow");
+    }
+    cmd.arg(&container_name)
+        .status()
+        .context("Failed to run podman logs")?;
+    Ok(())
+}
+
+pub fn launch_container(config: &AppConfig, opts: &LaunchOptions, rebuild: bool) -> Result<()> {
+    let container_name = opts.container_name();
+    let volume_name = opts.volume_name();
+
+    // Rebuilds change the image under the home volume; keep a copy to roll back to.
+    if rebuild
+        && let Err(e) =
+            volume::auto_snapshot(config, opts.workspace, &volume_name, SnapshotReason::Rebuild)
+    {
+        eprintln!("{} {e:#}", "Could not snapshot home volume:".yellow());
+    }
 
     // Handle rebuild: remove the container (but keep volume)
     if rebuild && container_exists(&container_name)? {
@@ -265,26 +1028,37 @@ pub fn launch_container(
             .status();
     }
 
-    // Init home volume if it doesn't exist
+    let running = container_is_running(&container_name)?;
+
+    // Init home volume if it doesn't exist, otherwise bring in host config changes
     if !volume_exists(&volume_name)? {
-        init_home_volume(config, &volume_name, &container_name, image)?;
+        init_home_volume(config, &volume_name, &container_name, opts)?;
+    } else if !running {
+        resync_or_warn(config, opts, &volume_name, &container_name);
     }
 
-    if container_is_running(&container_name)? {
+    let session = history::Session::new(
+        opts.workspace,
+        opts.session,
+        opts.image,
+        &container_name,
+        None,
+    );
+    if running {
         // Reconnect to existing running container
         println!(
             "{} {}",
             "Attaching to running container:".green(),
             container_name
         );
-        Command::new("podman")
-            .args(["attach", &container_name])
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .context("Failed to attach to container")?;
-        // Non-zero exits (detach=0, ctrl+c=130) are intentionally ignored
+        if opts.read_only_workspace {
+            println!(
+                "{}",
+                "--read-only-workspace has no effect on an already running container.".yellow()
+            );
+        }
+        let session = history::reattach(&config.history_file, session);
+        history::track(config, session, || podman_attach(&container_name))?;
     } else {
         // Clean up stale stopped container if one exists
         if container_exists(&container_name)? {
@@ -295,37 +1069,22 @@ pub fn launch_container(
 
         println!("{} {}", "Starting container:".blue().bold(), container_name);
 
-        let proj = project_name(workspace);
+        let (session_args, egress) =
+            session_run_args(config, opts, &container_name, &volume_name, &session.id)?;
         let mut run_args: Vec<String> = vec![
-            "run".into(),
             "--rm".into(),
             "-it".into(),
             "--name".into(),
             container_name.clone(),
-            "-v".into(),
-            format!("{}:/home/claude:z", volume_name),
-            "-v".into(),
-            format!("{}:/app:Z", workspace_str),
-            "--add-host=host.containers.internal:host-gateway".into(),
-            "-e".into(),
-            "HOST_GATEWAY=host.containers.internal".into(),
-            "-e".into(),
-            format!("NOTIFY_URL=http://host.containers.internal:{}/notify?project={}", port, proj),
         ];
-        for p in ports {
-            run_args.push("-p".into());
-            run_args.push(p.clone());
-        }
-        run_args.push(image.to_string());
+        run_args.extend(session_args);
+        run_args.push(opts.image.to_string());
 
-        Command::new("podman")
-            .args(&run_args)
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .context("Failed to run container")?;
+        history::track(config, session, || {
+            podman_run(&run_args, &container_name, egress.as_ref())
+        })?;
         // Non-zero exits intentionally ignored
+        finish_run(config, &container_name, egress)?;
     }
 
     Ok(())
@@ -333,20 +1092,19 @@ pub fn launch_container(
 
 pub fn run_in_container(
     config: &AppConfig,
-    workspace: &Path,
-    port: u16,
+    opts: &LaunchOptions,
     command: &str,
     args: &[String],
-    ports: &[String],
 ) -> Result<()> {
-    let container_name = generate_container_name(workspace);
-    let volume_name = generate_volume_name(workspace);
-    let image = crate::image::image_name(workspace);
-    let workspace_str = workspace.to_string_lossy();
+    let container_name = opts.container_name();
+    let volume_name = opts.volume_name();
 
-    // Init home volume if it doesn't exist
+    // Init home volume if it doesn't exist, otherwise bring in host config changes
+    // unless the session's container is using it right now
     if !volume_exists(&volume_name)? {
-        init_home_volume(config, &volume_name, &container_name, &image)?;
+        init_home_volume(config, &volume_name, &container_name, opts)?;
+    } else if !container_is_running(&container_name)? {
+        resync_or_warn(config, opts, &volume_name, &container_name);
     }
 
     println!(
@@ -356,75 +1114,147 @@ pub fn run_in_container(
         command
     );
 
-    let proj = project_name(workspace);
+    // One-off commands may run next to the session's container, so they get their own.
+    let run_name = format!(
+        "{}-run-{}",
+        container_name,
+        &uuid::Uuid::new_v4().simple().to_string()[..6]
+    );
+    let mut command_line = vec![command.to_string()];
+    command_line.extend_from_slice(args);
+    let session = history::Session::new(
+        opts.workspace,
+        opts.session,
+        opts.image,
+        &run_name,
+        Some(command_line.join(" ")),
+    );
+    let (session_args, egress) =
+        session_run_args(config, opts, &run_name, &volume_name, &session.id)?;
     let mut run_args: Vec<String> = vec![
-        "run".into(),
         "--rm".into(),
         "-it".into(),
-        "-v".into(),
-        format!("{}:/home/claude", volume_name),
-        "-v".into(),
-        format!("{}:/app:Z", workspace_str),
-        "--add-host=host.containers.internal:host-gateway".into(),
-        "-e".into(),
-        "HOST_GATEWAY=host.containers.internal".into(),
-        "-e".into(),
-        format!("NOTIFY_URL=http://host.containers.internal:{}/notify?project={}", port, proj),
+        "--name".into(),
+        run_name.clone(),
     ];
-    for p in ports {
-        run_args.push("-p".into());
-        run_args.push(p.clone());
-    }
+    run_args.extend(session_args);
     run_args.extend_from_slice(&[
         "--entrypoint".into(),
         command.to_string(),
-        image,
+        opts.image.to_string(),
     ]);
     run_args.extend_from_slice(args);
 
-    let status = Command::new("podman")
-        .args(&run_args)
-        .stdin(std::process::Stdio::inherit())
-        .stdout(std::process::Stdio::inherit())
-        .stderr(std::process::Stdio::inherit())
-        .status()
-        .context("Failed to run command in container")?;
+    let exit_code = history::track(config, session, || {
+        podman_run(&run_args, &run_name, egress.as_ref())
+            .context("Failed to run command in container")
+    })?;
+    finish_run(config, &run_name, egress)?;
 
-    if !status.success() {
+    if exit_code != Some(0) {
         anyhow::bail!("Command exited with non-zero status");
     }
 
     Ok(())
 }
 
-pub fn list_containers() -> Result<()> {
+/// One row of `ai-pod list`, read from `podman ps --format json`.
+#[derive(Debug, PartialEq)]
+pub struct ContainerEntry {
+    pub name: String,
+    pub session: String,
+    pub status: String,
+    pub limits: String,
+    pub created: String,
+}
+
+/// Parses `podman ps --format json` output into entries grouped by workspace label.
+/// Containers created before sessions existed have no label and are grouped separately.
+fn group_containers(raw: &str) -> Result<BTreeMap<String, Vec<ContainerEntry>>> {
+    let containers: Vec<serde_json::Value> =
+        serde_json::from_str(raw).context("Failed to parse podman ps output")?;
+    let mut groups: BTreeMap<String, Vec<ContainerEntry>> = BTreeMap::new();
+
+    for container in &containers {
+        let str_field = |key: &str| container[key].as_str().unwrap_or("").to_string();
+        let label = |key: &str| container["Labels"][key].as_str().map(str::to_string);
+
+        let name = container["Names"][0].as_str().unwrap_or("").to_string();
+        let status = match str_field("Status") {
+            s if s.is_empty() => str_field("State"),
+            s => s,
+        };
+        let workspace = label(WORKSPACE_LABEL).unwrap_or_else(|| "(unknown workspace)".into());
+        groups.entry(workspace).or_default().push(ContainerEntry {
+            name,
+            session: label(SESSION_LABEL).unwrap_or_else(|| "-".into()),
+            status,
+            limits: label("ai-pod.limits").unwrap_or_default(),
+            created: str_field("CreatedAt"),
+        });
+    }
+
+    for entries in groups.values_mut() {
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    Ok(groups)
+}
+
+/// ai-pod containers grouped by workspace; stopped ones only if `all` is set.
+pub fn query_containers(all: bool) -> Result<BTreeMap<String, Vec<ContainerEntry>>> {
+    let mut args = vec!["ps", "--filter", "name=^claude-", "--format", "json"];
+    if all {
+        args.insert(1, "-a");
+    }
     let output = Command::new("podman")
-        .args([
-            "ps",
-            "-a",
-            "--filter",
-            "name=^claude-",
-            "--format",
-            "{{.Names}}\t{{.Status}}\t{{.CreatedAt}}",
-        ])
+        .args(&args)
         .output()
         .context("Failed to list containers")?;
 
-    if output.stdout.is_empty() {
+    let raw = String::from_utf8_lossy(&output.stdout);
+    if raw.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    group_containers(&raw)
+}
+
+pub fn list_containers() -> Result<()> {
+    let groups = query_containers(true)?;
+
+    if groups.is_empty() {
         println!("{}", "No claude containers found.".yellow());
-    } else {
-        println!("{}", "Claude containers:".blue().bold());
-        println!("{:<20} {:<30} {}", "NAME", "STATUS", "CREATED");
-        println!("{}", "-".repeat(80));
-        print!("{}", String::from_utf8_lossy(&output.stdout));
+        return Ok(());
+    }
+
+    println!("{}", "Claude containers:".blue().bold());
+    for (workspace, entries) in &groups {
+        println!("\n{}", workspace.bold());
+        println!(
+            "  {:<28} {:<12} {:<24} {:<36} CREATED",
+            "NAME", "SESSION", "STATUS", "LIMITS"
+        );
+        for entry in entries {
+            println!(
+                "  {:<28} {:<12} {:<24} {:<36} {}",
+                entry.name, entry.session, entry.status, entry.limits, entry.created
+            );
+        }
     }
 
     Ok(())
 }
 
-pub fn clean_container(workspace: &Path) -> Result<()> {
-    let container_name = generate_container_name(workspace);
-    let volume_name = generate_volume_name(workspace);
+/// Removes the container of a session and its home volume. For a named session only
+/// its own volume is removed; a home volume shared via `--share-home` is kept.
+/// Unless `snapshot` is false, the volume is snapshotted before it is deleted.
+pub fn clean_container(
+    config: &AppConfig,
+    workspace: &Path,
+    session: Option<&str>,
+    snapshot: bool,
+) -> Result<()> {
+    let container_name = session_container_name(workspace, session);
+    let volume_name = session_volume_name(workspace, session);
 
     let container_existed = container_exists(&container_name)?;
 
@@ -442,6 +1272,7 @@ pub fn clean_container(workspace: &Path) -> Result<()> {
             .args(["rm", &container_name])
             .status()
             .context("Failed to remove container")?;
+        auth::revoke(&config.token_dir, &container_name);
 
         println!("{}", "Container removed.".green());
     } else {
@@ -454,6 +1285,11 @@ pub fn clean_container(workspace: &Path) -> Result<()> {
 
     // Remove named home volume
     if volume_exists(&volume_name)? {
+        if snapshot {
+            volume::snapshot(config, workspace, &volume_name, SnapshotReason::Clean).context(
+                "Failed to snapshot home volume; volume kept (use --no-snapshot to skip)",
+            )?;
+        }
         println!("{} {}", "Removing volume:".red().bold(), volume_name);
         let status = Command::new("podman")
             .args(["volume", "rm", &volume_name])
@@ -478,9 +1314,22 @@ mod tests {
         std::fs::create_dir_all(&config_dir).unwrap();
         AppConfig {
             pid_file: config_dir.join("server.pid"),
+            server_lock: config_dir.join("server.lock"),
+            start_lock: config_dir.join("start.lock"),
             log_file: config_dir.join("server.log"),
-            runtime_settings: config_dir.join("runtime-settings.json"),
-            runtime_claude_md: config_dir.join("runtime-CLAUDE.md"),
+            server_state: config_dir.join("server.json"),
+            config_file: config_dir.join("config.toml"),
+            events_file: config_dir.join("events.jsonl"),
+            token_dir: config_dir.join("tokens"),
+            server_token: config_dir.join("server.token"),
+            mutes_file: config_dir.join("mutes.json"),
+            trusted_policy: config_dir.join("trusted-policy.json"),
+            history_file: config_dir.join("history.jsonl"),
+            egress_log: config_dir.join("egress.log"),
+            sandbox_dir: config_dir.join("sandboxes"),
+            snapshot_dir: config_dir.join("snapshots"),
+            sync_dir: config_dir.join("sync"),
+            toolchain_cache: config_dir.join("toolchains.json"),
             config_dir,
             home_dir: home,
         }
@@ -528,19 +1377,166 @@ mod tests {
         assert_ne!(a, b);
     }
 
+    fn session_opts<'a>(
+        workspace: &'a Path,
+        project: &'a ProjectConfig,
+        session: Option<&'a str>,
+        share_home: bool,
+    ) -> LaunchOptions<'a> {
+        LaunchOptions {
+            workspace,
+            image: "img",
+            notify_port: 9876,
+            ports: &[],
+            project,
+            read_only_workspace: false,
+            sandbox: None,
+            session,
+            share_home,
+        }
+    }
+
+    #[test]
+    fn synced_settings_keep_container_edits() {
+        let base = r#"{"model": "sonnet", "env": {"A": "1"}}"#;
+        let ours = r#"{"model": "opus", "env": {"A": "1"}}"#;
+        let theirs = r#"{"model": "sonnet", "env": {"A": "2"}}"#;
+        let (update, report) =
+            merge_synced_file("settings.json", Some(base), Some(ours), theirs).unwrap();
+        let merged: serde_json::Value = serde_json::from_str(&update.unwrap()).unwrap();
+        assert_eq!(merged, serde_json::json!({"model": "opus", "env": {"A": "2"}}));
+        assert_eq!(report.updated, vec!["env.A"]);
+    }
+
+    #[test]
+    fn synced_file_without_changes_is_left_alone() {
+        let (update, report) =
+            merge_synced_file("CLAUDE.md", Some("a\n"), Some("a\nmine\n"), "a\n").unwrap();
+        assert!(update.is_none());
+        assert_eq!(report, MergeReport::default());
+    }
+
+    #[test]
+    fn missing_synced_file_is_recreated() {
+        let (update, _) = merge_synced_file("CLAUDE.md", None, None, "host\n").unwrap();
+        assert_eq!(update.as_deref(), Some("host\n"));
+    }
+
+    #[test]
+    fn unrecorded_volumes_take_the_host_copy_on_first_sync() {
+        let dir = TempDir::new().unwrap();
+        let ours = "old rules\n";
+        let base = sync_base(dir.path(), "CLAUDE.md", Some(ours));
+        let (update, _) =
+            merge_synced_file("CLAUDE.md", base.as_deref(), Some(ours), "new rules\n").unwrap();
+        assert_eq!(update.as_deref(), Some("new rules\n"));
+
+        std::fs::write(dir.path().join("CLAUDE.md"), "recorded\n").unwrap();
+        let base = sync_base(dir.path(), "CLAUDE.md", Some(ours));
+        assert_eq!(base.as_deref(), Some("recorded\n"));
+    }
+
+    #[test]
+    fn invalid_container_settings_are_not_overwritten() {
+        let (update, report) =
+            merge_synced_file("settings.json", None, Some("{oops"), "{}").unwrap();
+        assert!(update.is_none());
+        assert_eq!(report.conflicts.len(), 1);
+    }
+
+    #[test]
+    fn named_session_gets_own_container_and_volume() {
+        let path = Path::new("/home/user/myproject");
+        let project = ProjectConfig::default();
+        let default = session_opts(path, &project, None, false);
+        let named = session_opts(path, &project, Some("tests"), false);
+        assert_eq!(default.container_name(), generate_container_name(path));
+        assert_eq!(
+            named.container_name(),
+            format!("{}-tests", generate_container_name(path))
+        );
+        assert_eq!(default.volume_name(), generate_volume_name(path));
+        assert_eq!(
+            named.volume_name(),
+            format!("{}-tests-home", generate_container_name(path))
+        );
+    }
+
+    #[test]
+    fn share_home_uses_workspace_volume() {
+        let path = Path::new("/home/user/myproject");
+        let project = ProjectConfig::default();
+        let opts = session_opts(path, &project, Some("tests"), true);
+        assert_eq!(opts.volume_name(), generate_volume_name(path));
+        assert_ne!(opts.container_name(), generate_container_name(path));
+    }
+
+    #[test]
+    fn session_labels_record_workspace_and_session() {
+        let path = Path::new("/home/user/myproject");
+        let project = ProjectConfig::default();
+        let labels = session_opts(path, &project, None, false).labels();
+        assert!(labels.contains(&"--label=ai-pod.workspace=/home/user/myproject".to_string()));
+        assert!(labels.contains(&"--label=ai-pod.session=default".to_string()));
+    }
+
+    #[test]
+    fn session_name_validation() {
+        assert!(validate_session_name("tests").is_ok());
+        assert!(validate_session_name("review_2").is_ok());
+        assert!(validate_session_name("").is_err());
+        assert!(validate_session_name("Tests").is_err());
+        assert!(validate_session_name("-x").is_err());
+        assert!(validate_session_name("a/b").is_err());
+        assert!(validate_session_name("default").is_err());
+        assert!(validate_session_name("init").is_err());
+    }
+
+    #[test]
+    fn helper_containers_never_share_a_session_name() {
+        let path = Path::new("/home/user/myproject");
+        let helper = helper_container_name(&session_container_name(path, None), "sync");
+        assert_ne!(helper, session_container_name(path, Some("sync")));
+        let session = helper.rsplit_once('-').unwrap().1;
+        assert!(validate_session_name(session).is_err());
+    }
+
+    #[test]
+    fn containers_are_grouped_by_workspace() {
+        let raw = r#"[
+            {"Names": ["claude-aaa-tests"], "Status": "Up 2 minutes", "CreatedAt": "2 minutes ago",
+             "Labels": {"ai-pod.workspace": "/w/a", "ai-pod.session": "tests", "ai-pod.limits": "mem=8g"}},
+            {"Names": ["claude-aaa"], "Status": "", "State": "exited", "CreatedAt": "1 hour ago",
+             "Labels": {"ai-pod.workspace": "/w/a", "ai-pod.session": "default"}},
+            {"Names": ["claude-bbb"], "Status": "Up 1 hour", "CreatedAt": "1 hour ago", "Labels": null}
+        ]"#;
+        let groups = group_containers(raw).unwrap();
+        assert_eq!(groups.len(), 2);
+
+        let a = &groups["/w/a"];
+        assert_eq!(a[0].name, "claude-aaa");
+        assert_eq!(a[0].status, "exited");
+        assert_eq!(a[1].session, "tests");
+        assert_eq!(a[1].limits, "mem=8g");
+
+        let legacy = &groups["(unknown workspace)"];
+        assert_eq!(legacy[0].session, "-");
+    }
+
     #[test]
     fn runtime_settings_contains_stop_hook() {
         let dir = TempDir::new().unwrap();
         let config = make_test_config(&dir);
-        generate_runtime_settings(&config).unwrap();
+        generate_runtime_settings(&config, &ProjectConfig::default(), dir.path()).unwrap();
 
-        let content = std::fs::read_to_string(&config.runtime_settings).unwrap();
+        let content = std::fs::read_to_string(dir.path().join("settings.json")).unwrap();
         let json: serde_json::Value = serde_json::from_str(&content).unwrap();
 
         let stop = &json["hooks"]["Stop"];
         assert!(stop.is_array(), "hooks.Stop should be an array");
         let cmd = stop[0]["hooks"][0]["command"].as_str().unwrap();
         assert!(cmd.contains("NOTIFY_URL"));
+        assert!(json["hooks"]["Notification"].is_array());
     }
 
     #[test]
@@ -558,21 +1554,57 @@ mod tests {
         )
         .unwrap();
 
-        generate_runtime_settings(&config).unwrap();
+        generate_runtime_settings(&config, &ProjectConfig::default(), dir.path()).unwrap();
 
-        let content = std::fs::read_to_string(&config.runtime_settings).unwrap();
+        let content = std::fs::read_to_string(dir.path().join("settings.json")).unwrap();
         let json: serde_json::Value = serde_json::from_str(&content).unwrap();
         assert_eq!(json["theme"], "dark");
         assert_eq!(json["verbosity"], "verbose");
     }
 
+    #[test]
+    fn malformed_host_settings_fall_back_to_defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = make_test_config(&dir);
+        std::fs::create_dir_all(config.home_dir.join(".claude")).unwrap();
+        std::fs::write(config.claude_settings_path(), "{\"theme\": ").unwrap();
+
+        let host_ok =
+            generate_runtime_settings(&config, &ProjectConfig::default(), dir.path()).unwrap();
+
+        assert!(!host_ok);
+        let content = std::fs::read_to_string(dir.path().join("settings.json")).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert!(json["hooks"]["Stop"].is_array());
+    }
+
+    #[test]
+    fn runtime_settings_include_project_permissions() {
+        let dir = TempDir::new().unwrap();
+        let config = make_test_config(&dir);
+        let project =
+            ProjectConfig::parse("[claude.permissions]\nallow = [\"Bash(make:*)\"]").unwrap();
+
+        assert!(generate_runtime_settings(&config, &project, dir.path()).unwrap());
+
+        let content = std::fs::read_to_string(dir.path().join("settings.json")).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(json["permissions"]["allow"][0], "Bash(make:*)");
+    }
+
+    /// Generates the runtime CLAUDE.md into the test home, the root of the temp dir.
+    fn write_claude_md(config: &AppConfig, workspace: &Path, project: &ProjectConfig) {
+        let opts = session_opts(workspace, project, None, false);
+        generate_runtime_claude_md(config, &opts, &[], &config.home_dir).unwrap();
+    }
+
     #[test]
     fn runtime_claude_md_contains_container_preamble() {
         let dir = TempDir::new().unwrap();
         let config = make_test_config(&dir);
-        generate_runtime_claude_md(&config).unwrap();
+        write_claude_md(&config, dir.path(), &ProjectConfig::default());
 
-        let content = std::fs::read_to_string(&config.runtime_claude_md).unwrap();
+        let content = std::fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
         assert!(content.contains("host.containers.internal"));
         assert!(content.contains("Podman container"));
     }
@@ -586,20 +1618,181 @@ mod tests {
         std::fs::create_dir_all(&claude_dir).unwrap();
         std::fs::write(config.claude_md_path(), "# My Rules\nAlways use Rust.\n").unwrap();
 
-        generate_runtime_claude_md(&config).unwrap();
+        write_claude_md(&config, dir.path(), &ProjectConfig::default());
 
-        let content = std::fs::read_to_string(&config.runtime_claude_md).unwrap();
+        let content = std::fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
         assert!(content.contains("host.containers.internal"));
         assert!(content.contains("My Rules"));
         assert!(content.contains("Always use Rust."));
     }
 
+    #[test]
+    fn runtime_claude_md_lists_only_allowed_host_services() {
+        let dir = TempDir::new().unwrap();
+        let config = make_test_config(&dir);
+        let project = ProjectConfig::parse(
+            r#"host_access = [3000, { port = 5432, description = "Postgres" }]"#,
+        )
+        .unwrap();
+        write_claude_md(&config, dir.path(), &project);
+
+        let content = std::fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
+        assert!(content.contains("`host.containers.internal:3000`"));
+        assert!(content.contains("`host.containers.internal:5432` — Postgres"));
+        assert!(content.contains("all other host ports are blocked"));
+        assert!(!content.contains("curl http://host.containers.internal:3000"));
+    }
+
+    #[test]
+    fn host_access_args_default_maps_host_gateway() {
+        let args = host_access_args(&ProjectConfig::default(), 9876, None);
+        assert_eq!(args, vec!["--add-host=host.containers.internal:host-gateway"]);
+    }
+
+    #[test]
+    fn host_access_args_forward_only_declared_ports_and_notify_port() {
+        let project = ProjectConfig::parse("host_access = [5432, 3000]").unwrap();
+        let args = host_access_args(&project, 9876, None);
+        assert_eq!(
+            args,
+            vec![
+                "--network=pasta:--no-map-gw,-T,3000,-T,5432,-T,9876",
+                "--add-host=host.containers.internal:127.0.0.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn host_access_args_with_empty_list_still_allow_notify_server() {
+        let project = ProjectConfig::parse("host_access = []").unwrap();
+        let args = host_access_args(&project, 9876, None);
+        assert_eq!(args[0], "--network=pasta:--no-map-gw,-T,9876");
+    }
+
+    #[tokio::test]
+    async fn egress_proxy_is_forwarded_without_a_host_access_list() {
+        let dir = TempDir::new().unwrap();
+        let project = ProjectConfig::parse("[network]\nmode = \"none\"").unwrap();
+        let policy = EgressPolicy::from_config(&project.network).unwrap().unwrap();
+        let proxy = EgressProxy::start(policy, "app", &dir.path().join("egress.log")).unwrap();
+        let args = host_access_args(&project, 9876, Some(&proxy));
+        let mut ports = [proxy.port, 9876];
+        ports.sort_unstable();
+        assert_eq!(
+            args,
+            vec![
+                format!("--network=pasta:--no-map-gw,-T,{},-T,{}", ports[0], ports[1]),
+                "--add-host=host.containers.internal:127.0.0.1".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn isolating_the_network_flushes_both_route_tables() {
+        let commands = isolate_network_commands("4242");
+        assert_eq!(commands.len(), 2);
+        assert_eq!(
+            commands[0].join(" "),
+            "unshare nsenter -t 4242 -n ip -4 route flush table main"
+        );
+        assert_eq!(commands[1][6], "-6");
+    }
+
+    #[test]
+    fn workspace_mount_is_read_write_by_default() {
+        let dir = TempDir::new().unwrap();
+        let args = workspace_mount_args(dir.path(), false, &[], None).unwrap();
+        assert_eq!(args, vec!["-v".to_string(), format!("{}:/app:Z", dir.path().display())]);
+    }
+
+    #[test]
+    fn read_only_workspace_mounts_app_read_only() {
+        let dir = TempDir::new().unwrap();
+        let args = workspace_mount_args(dir.path(), true, &[".git/hooks".into()], None).unwrap();
+        assert_eq!(args, vec!["-v".to_string(), format!("{}:/app:ro,Z", dir.path().display())]);
+    }
+
+    #[test]
+    fn protected_paths_are_mounted_read_only() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join(".git/hooks")).unwrap();
+        let args =
+            workspace_mount_args(&root, false, &[".git/hooks/".into(), "infra".into()], None)
+                .unwrap();
+        assert_eq!(args.len(), 5);
+        assert_eq!(
+            args[3],
+            format!("{}:/app/.git/hooks:ro", root.join(".git/hooks").display())
+        );
+        assert_eq!(args[4], "--tmpfs=/app/infra:ro");
+    }
+
+    #[test]
+    fn protected_symlinks_are_mounted_where_they_point() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("deploy")).unwrap();
+        std::os::unix::fs::symlink(root.join("deploy"), root.join("infra")).unwrap();
+        let args = workspace_mount_args(&root, false, &["infra".into()], None).unwrap();
+        assert_eq!(
+            args[3],
+            format!("{}:/app/deploy:ro", root.join("deploy").display())
+        );
+
+        let outside = TempDir::new().unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("secrets")).unwrap();
+        let err = workspace_mount_args(&root, false, &["secrets".into()], None).unwrap_err();
+        assert!(err.to_string().contains("outside the workspace"));
+    }
+
+    #[test]
+    fn policy_files_are_mounted_read_only() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(project::PROJECT_CONFIG_NAME), "").unwrap();
+        let args = workspace_mount_args(dir.path(), false, &[], None).unwrap();
+        assert_eq!(
+            args[3],
+            format!(
+                "{}:/app/ai-pod.toml:ro",
+                dir.path().join("ai-pod.toml").display()
+            )
+        );
+        assert_eq!(args.len(), 4);
+    }
+
+    #[test]
+    fn protected_paths_must_stay_inside_workspace() {
+        let dir = TempDir::new().unwrap();
+        assert!(workspace_mount_args(dir.path(), false, &["../elsewhere".into()], None).is_err());
+        assert!(workspace_mount_args(dir.path(), false, &["/etc".into()], None).is_err());
+    }
+
     #[test]
     fn runtime_claude_md_without_existing_file_does_not_error() {
         let dir = TempDir::new().unwrap();
         let config = make_test_config(&dir);
         // No CLAUDE.md exists — should still succeed
-        generate_runtime_claude_md(&config).unwrap();
-        assert!(config.runtime_claude_md.exists());
+        write_claude_md(&config, dir.path(), &ProjectConfig::default());
+        assert!(dir.path().join("CLAUDE.md").exists());
+    }
+
+    #[test]
+    fn runtime_claude_md_includes_project_fragment_before_host_rules() {
+        let dir = TempDir::new().unwrap();
+        let config = make_test_config(&dir);
+        std::fs::create_dir_all(config.home_dir.join(".claude")).unwrap();
+        std::fs::write(config.claude_md_path(), "# My Rules\n").unwrap();
+        let workspace = dir.path().join("project");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("ai-pod.CLAUDE.md"), "Run tests with `make test`.\n").unwrap();
+
+        write_claude_md(&config, &workspace, &ProjectConfig::default());
+
+        let content = std::fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
+        let preamble = content.find("Podman container").unwrap();
+        let fragment = content.find("make test").unwrap();
+        let host = content.find("My Rules").unwrap();
+        assert!(preamble < fragment && fragment < host);
     }
 }
@@ -0,0 +1,636 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::project::{NetworkConfig, NetworkMode};
+
+/// Domains Claude Code itself needs; allowed under every restricted policy.
+const ALWAYS_ALLOWED: &[&str] = &["anthropic.com", "claude.ai"];
+
+/// Hosts that bypass the proxy inside the container (the notification server lives here).
+const NO_PROXY: &str = "host.containers.internal,localhost,127.0.0.1";
+
+/// Upper bound for a request head; anything larger is rejected.
+const MAX_HEAD_LEN: usize = 16 * 1024;
+
+const PROXY_USER: &str = "ai-pod";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EgressRule {
+    /// A domain name; also matches all of its subdomains.
+    Domain(String),
+    /// An address range; a bare IP address is a range with a full-length prefix.
+    Cidr(IpAddr, u8),
+}
+
+impl EgressRule {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let s = raw.trim();
+        if let Some((addr, prefix)) = s.split_once('/') {
+            let addr: IpAddr = addr.parse().with_context(|| format!("Invalid CIDR: {raw}"))?;
+            let prefix: u8 = prefix.parse().with_context(|| format!("Invalid CIDR: {raw}"))?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            if prefix > max {
+                anyhow::bail!("Invalid CIDR prefix: {raw}");
+            }
+            return Ok(Self::Cidr(addr, prefix));
+        }
+        if let Ok(addr) = s.parse::<IpAddr>() {
+            let prefix = if addr.is_ipv4() { 32 } else { 128 };
+            return Ok(Self::Cidr(addr, prefix));
+        }
+
+        let domain = s
+            .trim_start_matches("*.")
+            .trim_start_matches('.')
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if domain.is_empty() || domain.contains(['/', ':', ' ', '*']) {
+            anyhow::bail!("Invalid domain: {raw}");
+        }
+        Ok(Self::Domain(domain))
+    }
+}
+
+fn cidr_contains(net: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Addresses on the host or its local networks: loopback, private, carrier-grade NAT,
+/// link-local (including cloud metadata endpoints) and unique local IPv6.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_unspecified()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || cidr_contains(IpAddr::from([100, 64, 0, 0]), 10, ip)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal(v4.into()),
+            None => {
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    rules: Vec<EgressRule>,
+}
+
+impl EgressPolicy {
+    /// Builds the policy for a project's network config; `None` means unrestricted.
+    pub fn from_config(config: &NetworkConfig) -> Result<Option<Self>> {
+        let extra: &[String] = match config.mode {
+            NetworkMode::Full => return Ok(None),
+            NetworkMode::None => &[],
+            NetworkMode::Allowlist => &config.allow,
+        };
+
+        let mut rules: Vec<EgressRule> = ALWAYS_ALLOWED
+            .iter()
+            .map(|d| EgressRule::Domain(d.to_string()))
+            .collect();
+        for entry in extra {
+            rules.push(EgressRule::parse(entry)?);
+        }
+        Ok(Some(Self { rules }))
+    }
+
+    pub fn allows_domain(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.rules.iter().any(|r| match r {
+            EgressRule::Domain(d) => {
+                host == *d || host.strip_suffix(d.as_str()).is_some_and(|p| p.ends_with('.'))
+            }
+            EgressRule::Cidr(..) => false,
+        })
+    }
+
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.rules.iter().any(|r| match r {
+            EgressRule::Cidr(net, prefix) => cidr_contains(*net, *prefix, ip),
+            EgressRule::Domain(_) => false,
+        })
+    }
+
+    /// Whether `host` may be looked up. Names are only resolved when a domain rule allows
+    /// them or a CIDR rule might match their addresses, so a denied request can't smuggle
+    /// data out through the DNS query for its hostname.
+    pub fn may_resolve(&self, host: &str) -> bool {
+        host.parse::<IpAddr>().is_ok()
+            || self.allows_domain(host)
+            || self.rules.iter().any(|r| matches!(r, EgressRule::Cidr(..)))
+    }
+
+    /// Filters resolved addresses for `host` down to the ones this policy permits.
+    /// Internal addresses are only reachable through an explicit CIDR rule, so an
+    /// allowed name can't be pointed at the host, its local network or a metadata service.
+    pub fn permitted_addrs(&self, host: &str, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let domain_allowed = self.allows_domain(host);
+        addrs
+            .into_iter()
+            .filter(|a| self.allows_ip(a.ip()) || (domain_allowed && !is_internal(a.ip())))
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ProxyRequest {
+    method: String,
+    host: String,
+    port: u16,
+    authorization: Option<String>,
+    /// Request head to send upstream for plain HTTP requests (unused for CONNECT).
+    forward_head: String,
+}
+
+impl ProxyRequest {
+    fn is_connect(&self) -> bool {
+        self.method.eq_ignore_ascii_case("CONNECT")
+    }
+}
+
+fn split_authority(authority: &str, default_port: u16) -> Result<(String, u16)> {
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (host, after) = rest.split_once(']').context("Invalid IPv6 authority")?;
+        let port = match after.strip_prefix(':') {
+            Some(p) => p.parse().context("Invalid port")?,
+            None => default_port,
+        };
+        return Ok((host.to_string(), port));
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_string(), port.parse().context("Invalid port")?)),
+        None => Ok((authority.to_string(), default_port)),
+    }
+}
+
+fn parse_request(head: &str) -> Result<ProxyRequest> {
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().context("Empty request")?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().context("Missing method")?.to_string();
+    let target = parts.next().context("Missing request target")?;
+    let version = parts.next().unwrap_or("HTTP/1.1");
+
+    let (host, port, path) = if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = split_authority(target, 443)?;
+        (host, port, String::new())
+    } else {
+        let rest = target
+            .strip_prefix("http://")
+            .context("Only absolute http:// URLs can be proxied")?;
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = split_authority(authority, 80)?;
+        (host, port, path.to_string())
+    };
+
+    let mut authorization = None;
+    let mut forward_head = format!("{method} {path} {version}\r\n");
+    for line in lines.filter(|l| !l.is_empty()) {
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        if name.eq_ignore_ascii_case("proxy-authorization") {
+            authorization = Some(value.trim().to_string());
+        } else if !name.eq_ignore_ascii_case("proxy-connection") {
+            forward_head.push_str(line);
+            forward_head.push_str("\r\n");
+        }
+    }
+    forward_head.push_str("\r\n");
+
+    Ok(ProxyRequest {
+        method,
+        host,
+        port,
+        authorization,
+        forward_head,
+    })
+}
+
+/// Reads until the end of the request head; returns the head and any bytes read past it.
+async fn read_head(stream: &mut TcpStream) -> Result<(String, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("Connection closed before request head was complete");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return Ok((String::from_utf8_lossy(&buf[..end]).into_owned(), rest));
+        }
+        if buf.len() > MAX_HEAD_LEN {
+            anyhow::bail!("Request head too large");
+        }
+    }
+}
+
+struct ProxyState {
+    policy: EgressPolicy,
+    project: String,
+    log_file: PathBuf,
+    expected_auth: String,
+}
+
+impl ProxyState {
+    fn log_denied(&self, request: &ProxyRequest) {
+        let line = format!(
+            "{} DENY {} {} {}:{}\n",
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            self.project,
+            request.method,
+            request.host,
+            request.port
+        );
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_file);
+        if let Ok(mut file) = file {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+async fn respond(client: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    client.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+async fn handle_connection(mut client: TcpStream, state: &ProxyState) -> Result<()> {
+    let (head, rest) = read_head(&mut client).await?;
+    let request = match parse_request(&head) {
+        Ok(r) => r,
+        Err(e) => return respond(&mut client, "400 Bad Request", &format!("{e}\n")).await,
+    };
+
+    if request.authorization.as_deref() != Some(state.expected_auth.as_str()) {
+        let response = "HTTP/1.1 407 Proxy Authentication Required\r\n\
+                        Proxy-Authenticate: Basic realm=\"ai-pod\"\r\n\
+                        Content-Length: 0\r\nConnection: close\r\n\r\n";
+        client.write_all(response.as_bytes()).await?;
+        return Ok(());
+    }
+
+    let resolved: Vec<SocketAddr> = if state.policy.may_resolve(&request.host) {
+        tokio::net::lookup_host((request.host.as_str(), request.port))
+            .await
+            .map(|addrs| addrs.collect())
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let addrs = state.policy.permitted_addrs(&request.host, resolved);
+    if addrs.is_empty() {
+        state.log_denied(&request);
+        let body = format!(
+            "Blocked by ai-pod network policy: {}:{}\n",
+            request.host, request.port
+        );
+        return respond(&mut client, "403 Forbidden", &body).await;
+    }
+
+    let mut upstream = match TcpStream::connect(&addrs[..]).await {
+        Ok(s) => s,
+        Err(e) => return respond(&mut client, "502 Bad Gateway", &format!("{e}\n")).await,
+    };
+
+    if request.is_connect() {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    } else {
+        upstream.write_all(request.forward_head.as_bytes()).await?;
+    }
+    upstream.write_all(&rest).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Host-side HTTP(S) proxy enforcing an [`EgressPolicy`] for one container session.
+/// The proxy stops when this value is dropped.
+pub struct EgressProxy {
+    pub port: u16,
+    password: String,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl EgressProxy {
+    /// Binds the proxy to an ephemeral loopback port and serves it on the current tokio
+    /// runtime; the container reaches it through a pasta port forward. Denied requests
+    /// are appended to `log_file`.
+    pub fn start(policy: EgressPolicy, project: &str, log_file: &Path) -> Result<Self> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .context("The egress proxy requires a tokio runtime")?;
+
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .context("Failed to bind egress proxy")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+
+        let password = uuid::Uuid::new_v4().simple().to_string();
+        let credentials = base64::engine::general_purpose::STANDARD
+            .encode(format!("{PROXY_USER}:{password}"));
+        let state = Arc::new(ProxyState {
+            policy,
+            project: project.to_string(),
+            log_file: log_file.to_path_buf(),
+            expected_auth: format!("Basic {credentials}"),
+        });
+
+        let task = runtime.spawn(async move {
+            let Ok(listener) = TcpListener::from_std(listener) else {
+                return;
+            };
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, &state).await;
+                });
+            }
+        });
+
+        Ok(Self {
+            port,
+            password,
+            task,
+        })
+    }
+
+    /// Proxy URL (including credentials) as seen from inside the container, where the
+    /// proxy port is forwarded from the container's loopback.
+    pub fn url(&self) -> String {
+        format!(
+            "http://{PROXY_USER}:{}@127.0.0.1:{}",
+            self.password, self.port
+        )
+    }
+
+    /// `podman run` arguments that route the container's traffic through this proxy.
+    /// DNS is disabled as well; the container's routes are removed before it starts, so
+    /// the proxy is the only way out.
+    pub fn container_args(&self) -> Vec<String> {
+        let url = self.url();
+        let mut args = vec!["--dns=none".to_string()];
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            args.push("-e".into());
+            args.push(format!("{var}={url}"));
+        }
+        for var in ["NO_PROXY", "no_proxy"] {
+            args.push("-e".into());
+            args.push(format!("{var}={NO_PROXY}"));
+        }
+        args
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str]) -> EgressPolicy {
+        let config = NetworkConfig {
+            mode: NetworkMode::Allowlist,
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+        };
+        EgressPolicy::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn full_mode_has_no_policy() {
+        let config = NetworkConfig::default();
+        assert!(EgressPolicy::from_config(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn none_mode_still_allows_anthropic() {
+        let config = NetworkConfig {
+            mode: NetworkMode::None,
+            allow: vec!["github.com".into()],
+        };
+        let policy = EgressPolicy::from_config(&config).unwrap().unwrap();
+        assert!(policy.allows_domain("api.anthropic.com"));
+        assert!(!policy.allows_domain("github.com"));
+    }
+
+    #[test]
+    fn domain_rule_matches_subdomains_only_on_label_boundary() {
+        let p = policy(&["github.com"]);
+        assert!(p.allows_domain("github.com"));
+        assert!(p.allows_domain("api.github.com"));
+        assert!(p.allows_domain("API.GitHub.com."));
+        assert!(!p.allows_domain("evilgithub.com"));
+        assert!(!p.allows_domain("github.com.evil.net"));
+    }
+
+    #[test]
+    fn wildcard_prefix_is_accepted() {
+        assert_eq!(
+            EgressRule::parse("*.npmjs.org").unwrap(),
+            EgressRule::Domain("npmjs.org".into())
+        );
+    }
+
+    #[test]
+    fn cidr_rules_match_addresses() {
+        let p = policy(&["10.0.0.0/8", "192.168.1.5", "fd00::/8"]);
+        assert!(p.allows_ip("10.1.2.3".parse().unwrap()));
+        assert!(!p.allows_ip("11.0.0.1".parse().unwrap()));
+        assert!(p.allows_ip("192.168.1.5".parse().unwrap()));
+        assert!(!p.allows_ip("192.168.1.6".parse().unwrap()));
+        assert!(p.allows_ip("fd12::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn zero_prefix_matches_everything() {
+        let p = policy(&["0.0.0.0/0"]);
+        assert!(p.allows_ip("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(EgressRule::parse("10.0.0.0/33").is_err());
+        assert!(EgressRule::parse("http://example.com").is_err());
+        assert!(EgressRule::parse("").is_err());
+    }
+
+    #[test]
+    fn allowed_domain_cannot_reach_loopback() {
+        let p = policy(&["example.com"]);
+        let addrs = vec!["127.0.0.1:80".parse().unwrap(), "93.184.216.34:80".parse().unwrap()];
+        let permitted = p.permitted_addrs("example.com", addrs);
+        assert_eq!(permitted, vec!["93.184.216.34:80".parse().unwrap()]);
+    }
+
+    #[test]
+    fn allowed_domain_cannot_reach_internal_networks() {
+        let p = policy(&["example.com", "10.1.0.0/16"]);
+        let addrs = [
+            "169.254.169.254:80",
+            "192.168.1.10:80",
+            "100.64.0.1:80",
+            "10.2.0.1:80",
+            "10.1.0.1:80",
+            "[fd00::1]:80",
+            "[fe80::1]:80",
+            "[::ffff:172.16.0.1]:80",
+            "[2606:2800:220:1::1]:80",
+        ]
+        .iter()
+        .map(|a| a.parse().unwrap())
+        .collect();
+        let permitted = p.permitted_addrs("example.com", addrs);
+        assert_eq!(
+            permitted,
+            vec![
+                "10.1.0.1:80".parse().unwrap(),
+                "[2606:2800:220:1::1]:80".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn denied_names_are_not_resolved() {
+        let none = EgressPolicy::from_config(&NetworkConfig {
+            mode: NetworkMode::None,
+            allow: Vec::new(),
+        })
+        .unwrap()
+        .unwrap();
+        assert!(!none.may_resolve("c2VjcmV0.attacker.example"));
+        assert!(none.may_resolve("api.anthropic.com"));
+        assert!(none.may_resolve("203.0.113.7"));
+        assert!(policy(&["10.0.0.0/8"]).may_resolve("db.internal"));
+    }
+
+    #[test]
+    fn parses_connect_request() {
+        let head = "CONNECT github.com:443 HTTP/1.1\r\nHost: github.com:443\r\nProxy-Authorization: Basic abc";
+        let req = parse_request(head).unwrap();
+        assert!(req.is_connect());
+        assert_eq!(req.host, "github.com");
+        assert_eq!(req.port, 443);
+        assert_eq!(req.authorization.as_deref(), Some("Basic abc"));
+    }
+
+    #[test]
+    fn parses_absolute_http_request_and_strips_proxy_headers() {
+        let head = "GET http://example.com/a?b=1 HTTP/1.1\r\nHost: example.com\r\nProxy-Authorization: Basic abc\r\nProxy-Connection: keep-alive";
+        let req = parse_request(head).unwrap();
+        assert_eq!(req.host, "example.com");
+        assert_eq!(req.port, 80);
+        assert_eq!(
+            req.forward_head,
+            "GET /a?b=1 HTTP/1.1\r\nHost: example.com\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn parses_ipv6_authority() {
+        let req = parse_request("CONNECT [::1]:8443 HTTP/1.1").unwrap();
+        assert_eq!(req.host, "::1");
+        assert_eq!(req.port, 8443);
+    }
+
+    async fn serve_ok_once() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = read_head(&mut stream).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await;
+        });
+        port
+    }
+
+    fn client_for(proxy: &EgressProxy) -> reqwest::Client {
+        let proxy_url = format!("http://127.0.0.1:{}", proxy.port);
+        let proxy = reqwest::Proxy::http(proxy_url)
+            .unwrap()
+            .basic_auth(PROXY_USER, &proxy.password);
+        reqwest::Client::builder().proxy(proxy).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn proxy_forwards_allowed_and_blocks_denied_requests() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("egress.log");
+        let proxy = EgressProxy::start(policy(&["127.0.0.1"]), "demo", &log).unwrap();
+        let client = client_for(&proxy);
+
+        let port = serve_ok_once().await;
+        let resp = client
+            .get(format!("http://127.0.0.1:{port}/"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.text().await.unwrap(), "ok");
+
+        let resp = client.get("http://[::1]:9/").send().await.unwrap();
+        assert_eq!(resp.status(), 403);
+        let logged = std::fs::read_to_string(&log).unwrap();
+        assert!(logged.contains("DENY demo GET ::1:9"));
+    }
+
+    #[tokio::test]
+    async fn proxy_requires_credentials() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let proxy = EgressProxy::start(policy(&[]), "demo", &dir.path().join("log")).unwrap();
+        let proxy_url = format!("http://127.0.0.1:{}", proxy.port);
+        let client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::http(proxy_url).unwrap())
+            .build()
+            .unwrap();
+        let resp = client.get("http://claude.ai/").send().await.unwrap();
+        assert_eq!(resp.status(), 407);
+    }
+
+    #[test]
+    fn rejects_origin_form_requests() {
+        assert!(parse_request("GET /index.html HTTP/1.1\r\nHost: x").is_err());
+    }
+}
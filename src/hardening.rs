@@ -0,0 +1,217 @@
+use anyhow::Result;
+
+use crate::project::{HardeningConfig, HardeningProfile};
+
+/// Label holding the effective limits, shown by `ai-pod list`.
+const LIMITS_LABEL: &str = "ai-pod.limits";
+const PROFILE_LABEL: &str = "ai-pod.hardening";
+
+/// Effective security options and resource limits for a container.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimits {
+    pub profile: HardeningProfile,
+    pub memory: Option<String>,
+    pub cpus: Option<f64>,
+    pub pids_limit: Option<u32>,
+    pub read_only_root: bool,
+    pub cap_add: Vec<String>,
+}
+
+/// CPUs to hand to the default profile: all but one, so the host stays responsive.
+fn default_cpus() -> f64 {
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    available.saturating_sub(1).max(1) as f64
+}
+
+/// Accepts the sizes podman does: a number, optionally with a fraction, followed by an
+/// optional unit (`b`, `k`, `m`, `g`, `t` or `p`, any case, optionally as `kb`, `KiB`, ...).
+fn validate_memory(memory: &str) -> Result<()> {
+    let number_len = memory
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(memory.len());
+    let (number, unit) = memory.split_at(number_len);
+    let number_ok = !number.is_empty()
+        && number
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+    let unit = unit.strip_prefix(' ').unwrap_or(unit).to_ascii_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let unit = unit.strip_suffix('i').unwrap_or(unit);
+    let unit_ok = unit.is_empty() || matches!(unit, "k" | "m" | "g" | "t" | "p");
+    if !number_ok || !unit_ok {
+        anyhow::bail!("Invalid memory limit '{memory}' (expected e.g. 512m or 4g)");
+    }
+    Ok(())
+}
+
+impl ResourceLimits {
+    pub fn from_config(config: &HardeningConfig) -> Result<Self> {
+        let base = Self {
+            profile: config.profile,
+            memory: Some("8g".into()),
+            cpus: Some(default_cpus()),
+            pids_limit: Some(4096),
+            read_only_root: false,
+            cap_add: Vec::new(),
+        };
+
+        let has_overrides = config.memory.is_some()
+            || config.cpus.is_some()
+            || config.pids_limit.is_some()
+            || config.read_only_root.is_some()
+            || !config.cap_add.is_empty();
+
+        match config.profile {
+            HardeningProfile::Default | HardeningProfile::Strict if has_overrides => {
+                anyhow::bail!(
+                    "[hardening] limits are only used with profile = \"custom\" (current profile: {})",
+                    config.profile.as_str()
+                )
+            }
+            HardeningProfile::Default => Ok(base),
+            HardeningProfile::Strict => Ok(Self {
+                memory: Some("4g".into()),
+                cpus: Some(default_cpus().min(2.0)),
+                pids_limit: Some(1024),
+                read_only_root: true,
+                ..base
+            }),
+            HardeningProfile::Custom => {
+                if let Some(memory) = &config.memory {
+                    validate_memory(memory)?;
+                }
+                if config.cpus.is_some_and(|c| c <= 0.0) {
+                    anyhow::bail!("[hardening] cpus must be greater than 0");
+                }
+                Ok(Self {
+                    memory: config.memory.clone().or(base.memory),
+                    cpus: config.cpus.or(base.cpus),
+                    pids_limit: config.pids_limit.or(base.pids_limit),
+                    read_only_root: config.read_only_root.unwrap_or(base.read_only_root),
+                    cap_add: config.cap_add.clone(),
+                    ..base
+                })
+            }
+        }
+    }
+
+    /// Short human-readable summary, e.g. `mem=8g cpus=7 pids=4096`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(memory) = &self.memory {
+            parts.push(format!("mem={}", memory));
+        }
+        if let Some(cpus) = self.cpus {
+            parts.push(format!("cpus={}", cpus));
+        }
+        if let Some(pids) = self.pids_limit {
+            parts.push(format!("pids={}", pids));
+        }
+        if self.read_only_root {
+            parts.push("ro-root".into());
+        }
+        parts.join(" ")
+    }
+
+    /// `podman run` arguments applying these limits.
+    pub fn podman_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--cap-drop=ALL".to_string(),
+            "--security-opt=no-new-privileges".to_string(),
+        ];
+        for cap in &self.cap_add {
+            args.push(format!("--cap-add={}", cap));
+        }
+        if let Some(memory) = &self.memory {
+            args.push(format!("--memory={}", memory));
+        }
+        if let Some(cpus) = self.cpus {
+            args.push(format!("--cpus={}", cpus));
+        }
+        if let Some(pids) = self.pids_limit {
+            args.push(format!("--pids-limit={}", pids));
+        }
+        if self.read_only_root {
+            // /tmp, /var/tmp and /run stay writable via podman's default tmpfs mounts.
+            args.push("--read-only".into());
+        }
+        args.push(format!("--label={}={}", PROFILE_LABEL, self.profile.as_str()));
+        args.push(format!("--label={}={}", LIMITS_LABEL, self.summary()));
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::ProjectConfig;
+
+    fn limits(toml: &str) -> Result<ResourceLimits> {
+        ResourceLimits::from_config(&ProjectConfig::parse(toml).unwrap().hardening)
+    }
+
+    #[test]
+    fn default_profile_drops_caps_and_limits_resources() {
+        let args = limits("").unwrap().podman_args();
+        assert!(args.contains(&"--cap-drop=ALL".to_string()));
+        assert!(args.contains(&"--security-opt=no-new-privileges".to_string()));
+        assert!(args.contains(&"--memory=8g".to_string()));
+        assert!(args.contains(&"--pids-limit=4096".to_string()));
+        assert!(args.iter().any(|a| a.starts_with("--cpus=")));
+        assert!(!args.contains(&"--read-only".to_string()));
+    }
+
+    #[test]
+    fn strict_profile_uses_read_only_root() {
+        let l = limits("[hardening]\nprofile = \"strict\"").unwrap();
+        assert!(l.read_only_root);
+        assert_eq!(l.memory.as_deref(), Some("4g"));
+        assert_eq!(l.pids_limit, Some(1024));
+        assert!(l.cpus.unwrap() <= 2.0);
+        assert!(l.podman_args().contains(&"--read-only".to_string()));
+    }
+
+    #[test]
+    fn custom_profile_overrides_defaults() {
+        let l = limits(
+            "[hardening]\nprofile = \"custom\"\nmemory = \"2g\"\ncpus = 1.5\ncap_add = [\"NET_BIND_SERVICE\"]",
+        )
+        .unwrap();
+        assert_eq!(l.memory.as_deref(), Some("2g"));
+        assert_eq!(l.cpus, Some(1.5));
+        assert_eq!(l.pids_limit, Some(4096));
+        assert!(l.podman_args().contains(&"--cap-add=NET_BIND_SERVICE".to_string()));
+    }
+
+    #[test]
+    fn limits_require_custom_profile() {
+        assert!(limits("[hardening]\nmemory = \"2g\"").is_err());
+    }
+
+    #[test]
+    fn invalid_memory_is_rejected() {
+        assert!(limits("[hardening]\nprofile = \"custom\"\nmemory = \"lots\"").is_err());
+        assert!(limits("[hardening]\nprofile = \"custom\"\nmemory = \"4gg\"").is_err());
+        assert!(validate_memory("").is_err());
+        assert!(validate_memory("g").is_err());
+        assert!(validate_memory("1..5g").is_err());
+        assert!(validate_memory("-1g").is_err());
+    }
+
+    #[test]
+    fn podman_memory_units_are_accepted() {
+        let accepted = [
+            "512m", "4g", "4gb", "512MB", "2GiB", "1.5g", "1t", "1048576", "4 g",
+        ];
+        for memory in accepted {
+            assert!(validate_memory(memory).is_ok(), "{memory}");
+        }
+    }
+
+    #[test]
+    fn summary_lists_effective_limits() {
+        let l = limits("[hardening]\nprofile = \"custom\"\nmemory = \"1g\"\ncpus = 2.0\npids_limit = 100\nread_only_root = true")
+            .unwrap();
+        assert_eq!(l.summary(), "mem=1g cpus=2 pids=100 ro-root");
+    }
+}
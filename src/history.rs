@@ -0,0 +1,425 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use colored::{Color, Colorize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::container::{container_is_running, project_name};
+use crate::server::lifecycle::is_ai_pod_process;
+
+/// One container session started by `ai-pod`, as stored in `~/.ai-pod/history.jsonl`.
+/// A line is written when the session starts and another, with the same `id`, when it
+/// ends; sessions without the second line are still running or were interrupted. A
+/// detached session is recorded as such and continued by the next attach. The
+/// notification server adds a [`SessionEvent`] line for each event of the session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub workspace: PathBuf,
+    pub session: Option<String>,
+    pub image: String,
+    pub container: String,
+    /// Command run with `ai-pod run`; `None` for Claude itself.
+    pub command: Option<String>,
+    /// The `ai-pod` process waiting for the container.
+    pub pid: u32,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    /// Exit code of `podman`; `None` if it was killed by a signal or not recorded.
+    pub exit_code: Option<i32>,
+    /// Why `podman` could not be run, if it couldn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Notification events the container sent during the session, by kind. Counted
+    /// from the session's [`SessionEvent`] lines when reading the history.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub events: BTreeMap<String, usize>,
+    /// When `ai-pod` last detached from the still running container.
+    #[serde(default)]
+    pub detached: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn new(
+        workspace: &Path,
+        session: Option<&str>,
+        image: &str,
+        container: &str,
+        command: Option<String>,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            workspace: workspace.to_path_buf(),
+            session: session.map(str::to_string),
+            image: image.to_string(),
+            container: container.to_string(),
+            command,
+            pid: std::process::id(),
+            start: Utc::now(),
+            end: None,
+            exit_code: None,
+            error: None,
+            events: BTreeMap::new(),
+            detached: None,
+        }
+    }
+
+    /// Records the end of the session.
+    fn finish(&mut self, result: &Result<Option<i32>>, now: DateTime<Utc>) {
+        self.end = Some(now);
+        self.detached = None;
+        match result {
+            Ok(exit_code) => self.exit_code = *exit_code,
+            Err(e) => self.error = Some(format!("{e:#}")),
+        }
+    }
+
+    /// Whether an unfinished session is still going: its `ai-pod` process is waiting
+    /// for it, or it was detached and its container is still running.
+    fn is_live(&self) -> bool {
+        self.end.is_none()
+            && (is_ai_pod_process(self.pid)
+                || self.detached.is_some()
+                    && container_is_running(&self.container).unwrap_or(false))
+    }
+
+    /// Unknown for sessions that were interrupted before their end was recorded.
+    fn duration(&self, now: DateTime<Utc>, live: bool) -> Option<TimeDelta> {
+        match self.end {
+            Some(end) => Some(end - self.start),
+            None if live => Some(now - self.start),
+            None => None,
+        }
+    }
+}
+
+/// A notification event of the session `id`, appended by the notification server as it
+/// arrives, so counts don't depend on the events the server still holds.
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionEvent {
+    id: String,
+    event: String,
+}
+
+fn append(path: &Path, line: &impl Serialize) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(line)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Counts an event of kind `event` for the session `id`.
+pub fn record_event(path: &Path, id: &str, event: &str) -> Result<()> {
+    let line = SessionEvent {
+        id: id.to_string(),
+        event: event.to_string(),
+    };
+    append(path, &line)
+}
+
+fn append_or_warn(path: &Path, session: &Session) {
+    if let Err(e) = append(path, session) {
+        eprintln!("{} {e:#}", "Could not record session history:".yellow());
+    }
+}
+
+/// The session to record for attaching to the running `session.container`: the one
+/// that detached from it, if that is its latest session, otherwise `session` itself.
+pub fn reattach(path: &Path, session: Session) -> Session {
+    match read(path)
+        .into_iter()
+        .rfind(|s| s.container == session.container)
+    {
+        Some(earlier) if earlier.end.is_none() && earlier.detached.is_some() => Session {
+            pid: session.pid,
+            detached: None,
+            ..earlier
+        },
+        _ => session,
+    }
+}
+
+/// Records `session` around `run`, which returns the container's exit code. `podman`
+/// also returns when the user detaches; the session then stays open. A history that
+/// can't be written doesn't stop the session.
+pub fn track(
+    config: &AppConfig,
+    mut session: Session,
+    run: impl FnOnce() -> Result<Option<i32>>,
+) -> Result<Option<i32>> {
+    append_or_warn(&config.history_file, &session);
+    let result = run();
+    if result.is_ok() && container_is_running(&session.container).unwrap_or(false) {
+        session.detached = Some(Utc::now());
+    } else {
+        session.finish(&result, Utc::now());
+    }
+    append_or_warn(&config.history_file, &session);
+    result
+}
+
+/// Sessions in the order they started, each in its latest recorded state with the
+/// events counted for it.
+pub fn read(path: &Path) -> Vec<Session> {
+    let Ok(raw) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<Session> = Vec::new();
+    for line in raw.lines() {
+        if let Ok(mut session) = serde_json::from_str::<Session>(line) {
+            match sessions.iter_mut().rev().find(|s| s.id == session.id) {
+                Some(earlier) => {
+                    // Older versions wrote the counts into the end line instead.
+                    for (kind, n) in std::mem::take(&mut earlier.events) {
+                        *session.events.entry(kind).or_default() += n;
+                    }
+                    *earlier = session;
+                }
+                None => sessions.push(session),
+            }
+        } else if let Ok(event) = serde_json::from_str::<SessionEvent>(line)
+            && let Some(session) = sessions.iter_mut().rev().find(|s| s.id == event.id)
+        {
+            *session.events.entry(event.event).or_default() += 1;
+        }
+    }
+    sessions
+}
+
+fn select<'a>(
+    sessions: &'a [Session],
+    workspace: Option<&Path>,
+    since: Option<DateTime<Utc>>,
+) -> Vec<&'a Session> {
+    sessions
+        .iter()
+        .filter(|s| workspace.is_none_or(|w| s.workspace == w))
+        .filter(|s| since.is_none_or(|since| s.start >= since))
+        .collect()
+}
+
+fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", duration.num_seconds().max(0))
+    }
+}
+
+fn status(session: &Session, live: bool) -> (String, Color) {
+    match (session.end, session.exit_code) {
+        (None, _) if live && session.detached.is_some() => ("detached".into(), Color::Blue),
+        (None, _) if live => ("running".into(), Color::Blue),
+        (None, _) => ("interrupted".into(), Color::Yellow),
+        (Some(_), _) if session.error.is_some() => ("failed".into(), Color::Red),
+        (Some(_), Some(0)) => ("ok".into(), Color::Green),
+        (Some(_), Some(code)) => (format!("exit {}", code), Color::Red),
+        (Some(_), None) => ("killed".into(), Color::Red),
+    }
+}
+
+/// `ai-pod history`: sessions, newest last, with their total time.
+pub fn print_history(
+    config: &AppConfig,
+    workspace: Option<&Path>,
+    since: Option<DateTime<Utc>>,
+    limit: usize,
+) {
+    let sessions = read(&config.history_file);
+    let selected = select(&sessions, workspace, since);
+    if selected.is_empty() {
+        println!("{}", "No sessions recorded.".yellow());
+        return;
+    }
+    let now = Utc::now();
+    let live: Vec<bool> = selected.iter().map(|s| s.is_live()).collect();
+    let skip = selected.len().saturating_sub(limit);
+    for (session, &live) in selected[skip..].iter().zip(&live[skip..]) {
+        let mut name = project_name(&session.workspace);
+        if let Some(s) = &session.session {
+            name = format!("{} ({})", name, s);
+        }
+        if let Some(command) = &session.command {
+            name = format!("{}: {}", name, command);
+        }
+        let (status, color) = status(session, live);
+        let events: Vec<String> = session
+            .events
+            .iter()
+            .map(|(kind, n)| format!("{} {}", n, kind))
+            .collect();
+        if !events.is_empty() {
+            name = format!("{}  {}", name, events.join(", ").dimmed());
+        }
+        println!(
+            "{}  {:>8}  {}  {}",
+            session.start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            session
+                .duration(now, live)
+                .map_or("-".into(), format_duration),
+            format!("{:<11}", status).color(color),
+            name
+        );
+    }
+    let total = selected
+        .iter()
+        .zip(&live)
+        .filter_map(|(s, &live)| s.duration(now, live))
+        .fold(TimeDelta::zero(), |sum, d| sum + d);
+    println!(
+        "{} sessions, {} in total",
+        selected.len(),
+        format_duration(total)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn finished_sessions_replace_their_start_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let mut first = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        let second = Session::new(
+            Path::new("/src/web"),
+            Some("b"),
+            "img",
+            "claude-web-b",
+            None,
+        );
+        append(&path, &first).unwrap();
+        append(&path, &second).unwrap();
+
+        for (id, event) in [
+            (&first.id, "Stop"),
+            (&first.id, "Notification"),
+            (&second.id, "Stop"),
+            (&first.id, "Stop"),
+            (&"unknown".to_string(), "Stop"),
+        ] {
+            record_event(&path, id, event).unwrap();
+        }
+        let end = first.start + TimeDelta::minutes(90);
+        first.finish(&Ok(Some(0)), end);
+        append(&path, &first).unwrap();
+
+        let sessions = read(&path);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].end, Some(end));
+        assert_eq!(sessions[0].exit_code, Some(0));
+        assert_eq!(
+            sessions[0].events,
+            BTreeMap::from([("Notification".into(), 1), ("Stop".into(), 2)])
+        );
+        assert_eq!(
+            sessions[0].duration(end, false),
+            Some(TimeDelta::minutes(90))
+        );
+        assert_eq!(sessions[1].end, None);
+        assert_eq!(sessions[1].events, BTreeMap::from([("Stop".into(), 1)]));
+    }
+
+    #[test]
+    fn counts_written_by_older_versions_are_kept() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let mut session = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        append(&path, &session).unwrap();
+        session.finish(&Ok(Some(0)), session.start + TimeDelta::minutes(5));
+        session.events = BTreeMap::from([("Stop".into(), 3)]);
+        append(&path, &session).unwrap();
+        assert_eq!(read(&path)[0].events, session.events);
+    }
+
+    #[test]
+    fn sessions_that_could_not_run_are_failed() {
+        let mut session = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        let result = Err(anyhow::anyhow!("podman: not found"));
+        session.finish(&result, session.start);
+        assert_eq!(session.error.as_deref(), Some("podman: not found"));
+        assert_eq!(status(&session, false).0, "failed");
+
+        session.error = None;
+        session.exit_code = None;
+        assert_eq!(status(&session, false).0, "killed");
+    }
+
+    #[test]
+    fn reattaching_continues_the_detached_session() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let mut first = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        append(&path, &first).unwrap();
+        first.detached = Some(first.start + TimeDelta::minutes(10));
+        append(&path, &first).unwrap();
+        let other = Session::new(Path::new("/src/web"), None, "img", "claude-web", None);
+        append(&path, &other).unwrap();
+
+        let mut attach = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        attach.pid = 4242;
+        let resumed = reattach(&path, attach.clone());
+        assert_eq!(resumed.id, first.id);
+        assert_eq!(resumed.start, first.start);
+        assert_eq!((resumed.pid, resumed.detached), (4242, None));
+        assert_eq!(reattach(&path, other.clone()).id, other.id);
+
+        append(&path, &resumed).unwrap();
+        let mut ended = resumed;
+        ended.finish(&Ok(Some(0)), first.start + TimeDelta::minutes(30));
+        append(&path, &ended).unwrap();
+        assert_eq!(read(&path).len(), 2);
+        assert_eq!(ended.detached, None);
+        // A finished session isn't continued.
+        assert_eq!(reattach(&path, attach.clone()).id, attach.id);
+    }
+
+    #[test]
+    fn detached_sessions_are_not_reported_as_finished() {
+        let mut session = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        let now = session.start + TimeDelta::minutes(5);
+        assert_eq!(status(&session, true).0, "running");
+        assert_eq!(status(&session, false).0, "interrupted");
+        session.detached = Some(now);
+        assert_eq!(status(&session, true).0, "detached");
+        assert_eq!(session.duration(now, true), Some(TimeDelta::minutes(5)));
+        assert_eq!(session.duration(now, false), None);
+        session.finish(&Ok(Some(0)), now);
+        assert_eq!(status(&session, false).0, "ok");
+    }
+
+    #[test]
+    fn history_is_filtered_by_workspace_and_start() {
+        let mut old = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        old.start -= TimeDelta::days(10);
+        let recent = Session::new(Path::new("/src/app"), None, "img", "claude-app", None);
+        let other = Session::new(Path::new("/src/web"), None, "img", "claude-web", None);
+        let sessions = vec![old, recent, other];
+
+        assert_eq!(select(&sessions, None, None).len(), 3);
+        let app = select(&sessions, Some(Path::new("/src/app")), None);
+        assert_eq!(app.len(), 2);
+        let week = Utc::now() - TimeDelta::days(7);
+        let recent_app = select(&sessions, Some(Path::new("/src/app")), Some(week));
+        assert_eq!(recent_app, vec![&sessions[1]]);
+    }
+
+    #[test]
+    fn durations_are_formatted_compactly() {
+        assert_eq!(format_duration(TimeDelta::seconds(42)), "42s");
+        assert_eq!(format_duration(TimeDelta::minutes(12)), "12m");
+        assert_eq!(format_duration(TimeDelta::minutes(65)), "1h 05m");
+        assert_eq!(format_duration(TimeDelta::hours(26)), "26h 00m");
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+use crate::config::AppConfig;
+use crate::credentials;
+
+/// Entries of the host's `~/.claude` that are copied into a new home volume.
+/// Everything else (project histories, todos, shell snapshots, ...) stays on the host.
+pub const CLAUDE_DIR_ALLOWLIST: &[&str] = &[
+    "settings.json",
+    "CLAUDE.md",
+    "agents",
+    "commands",
+    ".credentials.json",
+];
+
+/// Files that hold Claude's own login and are expected to contain tokens.
+const AUTH_FILES: &[&str] = &[".credentials.json"];
+
+/// Top-level keys of `~/.claude.json` that are not copied: per-project history
+/// and MCP server definitions, which commonly embed API tokens.
+const CLAUDE_JSON_EXCLUDED_KEYS: &[&str] = &["projects", "mcpServers", "githubRepoPaths"];
+
+/// What a selective sync brought into (or kept out of) the home volume.
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    /// Staged files that were copied into the volume.
+    pub copied: Vec<PathBuf>,
+    /// Top-level `~/.claude` entries left out because they are not allowlisted.
+    pub omitted: Vec<String>,
+    /// Keys removed from `~/.claude.json`.
+    pub excluded_keys: Vec<String>,
+    /// Files dropped because the secret scan flagged them.
+    pub skipped: Vec<(PathBuf, Vec<&'static str>)>,
+}
+
+fn copy_tree(src: &Path, dest: &Path) -> Result<Vec<PathBuf>> {
+    let mut copied = Vec::new();
+    for entry in WalkDir::new(src).follow_links(false) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(src)?;
+        // `src` itself may be a plain file, in which case `relative` is empty.
+        let target = if relative.as_os_str().is_empty() {
+            dest.to_path_buf()
+        } else {
+            dest.join(relative)
+        };
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry.file_type().is_file() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+            copied.push(target);
+        }
+    }
+    Ok(copied)
+}
+
+/// Copies the allowlisted entries of `claude_dir` into `staging`.
+pub fn stage_claude_dir(claude_dir: &Path, staging: &Path, summary: &mut SyncSummary) -> Result<()> {
+    std::fs::create_dir_all(staging)?;
+
+    for entry in std::fs::read_dir(claude_dir).context("Failed to read ~/.claude")? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !CLAUDE_DIR_ALLOWLIST.contains(&name.as_str()) {
+            summary.omitted.push(name);
+            continue;
+        }
+        summary
+            .copied
+            .extend(copy_tree(&entry.path(), &staging.join(&name))?);
+    }
+
+    summary.omitted.sort();
+    Ok(())
+}
+
+/// Strips history and MCP server definitions from the contents of `~/.claude.json`.
+/// Returns the filtered JSON and the keys that were removed.
+pub fn filter_claude_json(raw: &str) -> Result<(String, Vec<String>)> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(raw).context("~/.claude.json is not valid JSON")?;
+    let obj = value
+        .as_object_mut()
+        .context("~/.claude.json is not an object")?;
+
+    let removed = CLAUDE_JSON_EXCLUDED_KEYS
+        .iter()
+        .filter(|k| obj.remove(**k).is_some())
+        .map(|k| k.to_string())
+        .collect();
+
+    Ok((serde_json::to_string_pretty(&value)?, removed))
+}
+
+/// Runs the secret scanner over staged files, ignoring Claude's own auth files.
+pub fn scan_staged(staging: &Path) -> Vec<(PathBuf, Vec<&'static str>)> {
+    credentials::scan_dir_for_secrets(staging)
+        .into_iter()
+        .filter(|(path, _)| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            !AUTH_FILES.contains(&name)
+        })
+        .collect()
+}
+
+fn print_summary(summary: &SyncSummary, staging: &Path) {
+    println!(
+        "{} {} file(s) from ~/.claude",
+        "Synced:".green(),
+        summary.copied.len()
+    );
+    for path in &summary.copied {
+        let relative = path.strip_prefix(staging).unwrap_or(path);
+        println!("  {} {}", "•".green(), relative.display());
+    }
+    if !summary.omitted.is_empty() {
+        println!(
+            "{} {}",
+            "Not copied (not allowlisted):".dimmed(),
+            summary.omitted.join(", ").dimmed()
+        );
+    }
+    if !summary.excluded_keys.is_empty() {
+        println!(
+            "{} {}",
+            "Stripped from ~/.claude.json:".dimmed(),
+            summary.excluded_keys.join(", ").dimmed()
+        );
+    }
+    for (path, kinds) in &summary.skipped {
+        let relative = path.strip_prefix(staging).unwrap_or(path);
+        println!(
+            "  {} {} ({})",
+            "skipped".yellow(),
+            relative.display(),
+            kinds.join(", ")
+        );
+    }
+}
+
+/// Stages the allowlisted parts of the host's Claude config, scans them for secrets
+/// and copies the result into the home volume mounted by `init_container`.
+pub fn sync_claude_home(config: &AppConfig, init_container: &str) -> Result<()> {
+    let staging = tempfile::TempDir::new().context("Failed to create staging directory")?;
+    let mut summary = SyncSummary::default();
+
+    let claude_dir = config.home_dir.join(".claude");
+    let staged_dir = staging.path().join(".claude");
+    if claude_dir.exists() {
+        stage_claude_dir(&claude_dir, &staged_dir, &mut summary)?;
+    }
+
+    let claude_json = config.home_dir.join(".claude.json");
+    let staged_json = staging.path().join(".claude.json");
+    if claude_json.exists() {
+        let raw = std::fs::read_to_string(&claude_json).context("Failed to read ~/.claude.json")?;
+        match filter_claude_json(&raw) {
+            Ok((filtered, removed)) => {
+                std::fs::write(&staged_json, filtered)?;
+                summary.copied.push(staged_json.clone());
+                summary.excluded_keys = removed;
+            }
+            Err(e) => eprintln!("{} {e:#}", "Skipping ~/.claude.json:".yellow()),
+        }
+    }
+
+    let flagged = scan_staged(staging.path());
+    if !flagged.is_empty() {
+        println!(
+            "\n{}",
+            "⚠  Potential secrets found in Claude config:".yellow().bold()
+        );
+        for (path, kinds) in &flagged {
+            let relative = path.strip_prefix(staging.path()).unwrap_or(path);
+            println!("  {} {} ({})", "•".yellow(), relative.display(), kinds.join(", "));
+        }
+        let copy_anyway = dialoguer::Confirm::new()
+            .with_prompt("Copy these files into the container anyway?")
+            .default(false)
+            .interact()?;
+        if !copy_anyway {
+            for (path, _) in &flagged {
+                std::fs::remove_file(path)?;
+                summary.copied.retain(|p| p != path);
+            }
+            summary.skipped = flagged;
+        }
+    }
+
+    // Soft errors: a failed copy leaves the volume usable, just without host config.
+    if staged_dir.exists() {
+        let _ = Command::new("podman")
+            .args([
+                "cp",
+                &format!("{}/.", staged_dir.to_string_lossy()),
+                &format!("{}:/home/claude/.claude/", init_container),
+            ])
+            .status();
+    }
+    if staged_json.exists() {
+        let _ = Command::new("podman")
+            .args([
+                "cp",
+                &staged_json.to_string_lossy(),
+                &format!("{}:/home/claude/", init_container),
+            ])
+            .status();
+    }
+
+    print_summary(&summary, staging.path());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn stage_copies_only_allowlisted_entries() {
+        let src = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        std::fs::write(src.path().join("settings.json"), "{}").unwrap();
+        std::fs::create_dir(src.path().join("agents")).unwrap();
+        std::fs::write(src.path().join("agents").join("reviewer.md"), "# r").unwrap();
+        std::fs::create_dir(src.path().join("projects")).unwrap();
+        std::fs::write(src.path().join("projects").join("x.jsonl"), "{}").unwrap();
+
+        let mut summary = SyncSummary::default();
+        stage_claude_dir(src.path(), dest.path(), &mut summary).unwrap();
+
+        assert!(dest.path().join("settings.json").exists());
+        assert!(dest.path().join("agents").join("reviewer.md").exists());
+        assert!(!dest.path().join("projects").exists());
+        assert_eq!(summary.copied.len(), 2);
+        assert_eq!(summary.omitted, vec!["projects".to_string()]);
+    }
+
+    #[test]
+    fn filter_claude_json_drops_history_and_mcp_servers() {
+        let raw = r#"{"oauthAccount": {"email": "a@b.c"}, "projects": {}, "mcpServers": {}}"#;
+        let (filtered, removed) = filter_claude_json(raw).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&filtered).unwrap();
+        assert_eq!(json["oauthAccount"]["email"], "a@b.c");
+        assert!(json.get("projects").is_none());
+        assert!(json.get("mcpServers").is_none());
+        assert_eq!(removed, vec!["projects".to_string(), "mcpServers".to_string()]);
+    }
+
+    #[test]
+    fn filter_claude_json_rejects_invalid_json() {
+        assert!(filter_claude_json("not json").is_err());
+    }
+
+    #[test]
+    fn scan_staged_ignores_auth_files() {
+        let dir = TempDir::new().unwrap();
+        let token = r#"{"accessToken": "sk-ant-REDACTED"}"#;
+        std::fs::write(dir.path().join(".credentials.json"), token).unwrap();
+        std::fs::write(dir.path().join("settings.json"), token).unwrap();
+
+        let flagged = scan_staged(dir.path());
+        assert_eq!(flagged.len(), 1);
+        assert!(flagged[0].0.ends_with("settings.json"));
+    }
+}
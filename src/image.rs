@@ -111,7 +111,7 @@ mod tests {
     #[test]
     fn image_name_short_hash_is_6_hex_chars() {
         let name = image_name(Path::new("/home/user/myproject"));
-        let hash_part = name.split('-').last().unwrap();
+        let hash_part = name.split('-').next_back().unwrap();
         assert_eq!(hash_part.len(), 6);
         assert!(hash_part.chars().all(|c| c.is_ascii_hexdigit()));
     }
@@ -2,17 +2,30 @@ mod cli;
 mod config;
 mod container;
 mod credentials;
+mod egress;
+mod hardening;
+mod history;
+mod home;
 mod image;
+mod merge;
+mod preamble;
+mod project;
+mod sandbox;
 mod server;
+mod settings;
 mod update;
+mod user_config;
+mod volume;
 
 use anyhow::{Context, Result};
 use clap::Parser;
 use colored::Colorize;
 use std::path::Path;
 
-use cli::{Cli, Command};
+use cli::{Cli, Command, LogsCommand, NotifyCommand, ServerCommand, VolumeCommand};
 use config::AppConfig;
+use project::ProjectConfig;
+use volume::SnapshotReason;
 
 fn resolve_workspace(workdir: &Option<std::path::PathBuf>) -> Result<std::path::PathBuf> {
     match workdir {
@@ -60,12 +73,16 @@ fn launch_flow(cli: &Cli) -> Result<()> {
         );
     }
 
+    if !project::confirm_policy(&config.trusted_policy, &workspace)? {
+        println!("{}", "Aborted.".red());
+        return Ok(());
+    }
+    let project = ProjectConfig::load(&workspace)?;
+
     // 3. Credential scan
-    if !cli.no_credential_check {
-        if !credentials::check_credentials(&workspace)? {
-            println!("{}", "Aborted.".red());
-            return Ok(());
-        }
+    if !cli.no_credential_check && !credentials::check_credentials(&workspace)? {
+        println!("{}", "Aborted.".red());
+        return Ok(());
     }
 
     // 4. Build image if needed
@@ -73,10 +90,39 @@ fn launch_flow(cli: &Cli) -> Result<()> {
     image::ensure_image(&config, &dockerfile, &image, cli.rebuild)?;
 
     // 5. Ensure notification server
-    server::lifecycle::ensure_server(&config.pid_file, &config.log_file, cli.notify_port)?;
+    let notify_port = server::lifecycle::ensure_server(&config, cli.notify_port)?;
+
+    // 6. Create sandbox if requested
+    let sandbox = if cli.sandbox {
+        Some(sandbox::Sandbox::create(&config, &workspace, cli.session.as_deref())?)
+    } else {
+        None
+    };
 
-    // 6. Launch container
-    container::launch_container(&config, &workspace, cli.notify_port, cli.rebuild, &image, &cli.ports)?;
+    // 7. Launch container
+    let opts = container::LaunchOptions {
+        workspace: &workspace,
+        image: &image,
+        notify_port,
+        ports: &cli.ports,
+        project: &project,
+        read_only_workspace: cli.read_only_workspace,
+        sandbox: sandbox.as_ref(),
+        session: cli.session.as_deref(),
+        share_home: cli.share_home,
+    };
+    let result = container::launch_container(&config, &opts, cli.rebuild);
+
+    // 8. Review sandbox changes, even if the container failed, unless the session was
+    //    only detached and the agent is still working in it
+    if let Some(sandbox) = &sandbox {
+        if container::container_is_running(&opts.container_name())? {
+            sandbox.keep();
+        } else {
+            sandbox.finish()?;
+        }
+    }
+    result?;
 
     Ok(())
 }
@@ -84,6 +130,9 @@ fn launch_flow(cli: &Cli) -> Result<()> {
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    if let Some(session) = &cli.session {
+        container::validate_session_name(session)?;
+    }
 
     // Skip update check for internal/daemon commands
     if !matches!(&cli.command, Some(Command::ServeNotifications)) {
@@ -115,22 +164,114 @@ async fn main() -> Result<()> {
             image::ensure_image(&config, &dockerfile, &image, cli.rebuild)?;
         }
         Some(Command::ServeNotifications) => {
-            server::run_server(cli.notify_port).await?;
+            let config = AppConfig::new()?;
+            config.init()?;
+            server::run_server(cli.notify_port, &config).await?;
         }
         Some(Command::StopServer) => {
             let config = AppConfig::new()?;
-            server::lifecycle::stop_server(&config.pid_file)?;
+            server::lifecycle::stop_server(&config.pid_file, cli.notify_port)?;
+        }
+        Some(Command::Server { action }) => {
+            let config = AppConfig::new()?;
+            config.init()?;
+            match action {
+                ServerCommand::Install => server::service::install(&config, cli.notify_port)?,
+                ServerCommand::Uninstall => server::service::uninstall()?,
+            }
         }
         Some(Command::ServerStatus) => {
             let config = AppConfig::new()?;
-            server::lifecycle::print_status(&config.pid_file, cli.notify_port);
+            server::lifecycle::print_status(&config, cli.notify_port);
+        }
+        Some(Command::Events { follow, lines }) => {
+            let config = AppConfig::new()?;
+            let port = server::lifecycle::current_port(&config, cli.notify_port);
+            server::events::tail(&config, port, *lines, *follow).await?;
+        }
+        Some(Command::History {
+            workspace,
+            since,
+            lines,
+        }) => {
+            let config = AppConfig::new()?;
+            let workspace = match workspace {
+                Some(_) => Some(resolve_workspace(workspace)?),
+                None => None,
+            };
+            let since = since
+                .as_deref()
+                .map(|d| {
+                    server::rules::offset(chrono::Utc::now(), -server::rules::parse_duration(d)?)
+                })
+                .transpose()?;
+            history::print_history(&config, workspace.as_deref(), since, *lines);
+        }
+        Some(Command::Logs { target }) => match target {
+            LogsCommand::Server { follow, lines } => {
+                let config = AppConfig::new()?;
+                server::log::tail(&config.log_file, *lines, *follow)?;
+            }
+            LogsCommand::Container { workdir, follow } => {
+                let workspace = resolve_workspace(workdir)?;
+                container::container_logs(&workspace, cli.session.as_deref(), *follow)?;
+            }
+        },
+        Some(Command::Attach { workdir }) => {
+            let workspace = resolve_workspace(workdir)?;
+            container::attach_container(&workspace, cli.session.as_deref())?;
+        }
+        Some(Command::Notify { action }) => {
+            let config = AppConfig::new()?;
+            config.init()?;
+            let project = |project: &Option<String>| -> Result<String> {
+                match project {
+                    Some(p) => Ok(p.clone()),
+                    None => Ok(container::project_name(&resolve_workspace(&None)?)),
+                }
+            };
+            match action {
+                NotifyCommand::Mute {
+                    project: p,
+                    duration,
+                } => server::rules::mute_command(&config, &project(p)?, duration.as_deref())?,
+                NotifyCommand::Unmute { project: p } => {
+                    server::rules::unmute_command(&config, &project(p)?)?
+                }
+                NotifyCommand::Status => server::rules::print_status(&config)?,
+            }
         }
         Some(Command::List) => {
             container::list_containers()?;
         }
-        Some(Command::Clean { workdir }) => {
+        Some(Command::Clean {
+            workdir,
+            no_snapshot,
+        }) => {
+            let config = AppConfig::new()?;
+            config.init()?;
             let workspace = resolve_workspace(workdir)?;
-            container::clean_container(&workspace)?;
+            container::clean_container(&config, &workspace, cli.session.as_deref(), !no_snapshot)?;
+        }
+        Some(Command::Volume { action }) => {
+            let config = AppConfig::new()?;
+            config.init()?;
+            let workspace = resolve_workspace(&cli.workdir)?;
+            let volume_name =
+                container::home_volume_name(&workspace, cli.session.as_deref(), cli.share_home);
+            match action {
+                VolumeCommand::Snapshot => {
+                    volume::snapshot(&config, &workspace, &volume_name, SnapshotReason::Manual)?;
+                }
+                VolumeCommand::List => volume::print_snapshots(&config, &volume_name)?,
+                VolumeCommand::Restore { snapshot } => {
+                    volume::restore(&config, &workspace, &volume_name, snapshot)?
+                }
+                VolumeCommand::Export { path } => volume::export(&volume_name, path)?,
+                VolumeCommand::Import { path } => {
+                    volume::import(&config, &workspace, &volume_name, path)?
+                }
+            }
         }
         Some(Command::Run { command, args }) => {
             let config = AppConfig::new()?;
@@ -144,16 +285,30 @@ async fn main() -> Result<()> {
                     workspace.display()
                 );
             }
-            if !cli.no_credential_check {
-                if !credentials::check_credentials(&workspace)? {
-                    println!("{}", "Aborted.".red());
-                    return Ok(());
-                }
+            if !project::confirm_policy(&config.trusted_policy, &workspace)? {
+                println!("{}", "Aborted.".red());
+                return Ok(());
+            }
+            let project = ProjectConfig::load(&workspace)?;
+            if !cli.no_credential_check && !credentials::check_credentials(&workspace)? {
+                println!("{}", "Aborted.".red());
+                return Ok(());
             }
             let image = image::image_name(&workspace);
             image::ensure_image(&config, &dockerfile, &image, cli.rebuild)?;
-            server::lifecycle::ensure_server(&config.pid_file, &config.log_file, cli.notify_port)?;
-            container::run_in_container(&config, &workspace, cli.notify_port, command, args, &cli.ports)?;
+            let notify_port = server::lifecycle::ensure_server(&config, cli.notify_port)?;
+            let opts = container::LaunchOptions {
+                workspace: &workspace,
+                image: &image,
+                notify_port,
+                ports: &cli.ports,
+                project: &project,
+                read_only_workspace: cli.read_only_workspace,
+                sandbox: None,
+                session: cli.session.as_deref(),
+                share_home: cli.share_home,
+            };
+            container::run_in_container(&config, &opts, command, args)?;
         }
         None => {
             launch_flow(&cli)?;
@@ -0,0 +1,177 @@
+use serde_json::{Map, Value};
+
+/// Outcome of a three-way merge between the last-synced host copy (`base`), the copy in
+/// the home volume (`ours`) and the freshly generated host copy (`theirs`).
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeReport {
+    /// Host changes applied to the volume copy, as JSON paths (`permissions.allow`) or
+    /// `(text)` for CLAUDE.md.
+    pub updated: Vec<String>,
+    /// Places where host and container both changed; the container version was kept.
+    pub conflicts: Vec<String>,
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn merge_value(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &str,
+    report: &mut MergeReport,
+) -> Option<Value> {
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+
+    // Objects are merged key by key so the report names the keys that changed;
+    // anything else is taken from the side that changed, or a conflict if both did.
+    let empty = Map::new();
+    match (base, ours, theirs) {
+        (None | Some(Value::Object(_)), Some(Value::Object(o)), Some(Value::Object(t))) => {
+            let b = base.and_then(Value::as_object).unwrap_or(&empty);
+            let mut keys: Vec<&String> = b.keys().chain(o.keys()).chain(t.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            let mut merged = Map::new();
+            for key in keys {
+                let value = merge_value(
+                    b.get(key),
+                    o.get(key),
+                    t.get(key),
+                    &join_path(path, key),
+                    report,
+                );
+                if let Some(value) = value {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Some(Value::Object(merged))
+        }
+        _ if base == ours => {
+            report.updated.push(path.to_string());
+            theirs.cloned()
+        }
+        _ => {
+            report.conflicts.push(path.to_string());
+            ours.cloned()
+        }
+    }
+}
+
+/// Merges JSON documents key by key. Without a `base` (nothing synced yet) both sides
+/// are treated as new, so only keys that differ become conflicts.
+pub fn merge_json(base: Option<&Value>, ours: &Value, theirs: &Value) -> (Value, MergeReport) {
+    let mut report = MergeReport::default();
+    let merged = merge_value(base, Some(ours), Some(theirs), "", &mut report)
+        .unwrap_or_else(|| ours.clone());
+    (merged, report)
+}
+
+/// Merges text line by line. On conflicting edits the container copy is kept unchanged.
+pub fn merge_text(base: Option<&str>, ours: &str, theirs: &str) -> (String, MergeReport) {
+    let mut report = MergeReport::default();
+    if ours == theirs || base == Some(theirs) {
+        return (ours.to_string(), report);
+    }
+    match diffy::merge(base.unwrap_or(""), ours, theirs) {
+        Ok(merged) => {
+            report.updated.push("(text)".into());
+            (merged, report)
+        }
+        Err(_) => {
+            report.conflicts.push("(text)".into());
+            (ours.to_string(), report)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn host_changes_are_applied_and_container_edits_kept() {
+        let base = json!({"model": "sonnet", "permissions": {"allow": ["Bash(ls)"]}});
+        let ours = json!({"model": "opus", "permissions": {"allow": ["Bash(ls)"]}});
+        let theirs = json!({"model": "sonnet", "permissions": {"allow": ["Bash(ls)", "Read"]}});
+
+        let (merged, report) = merge_json(Some(&base), &ours, &theirs);
+        assert_eq!(
+            merged,
+            json!({"model": "opus", "permissions": {"allow": ["Bash(ls)", "Read"]}})
+        );
+        assert_eq!(report.updated, vec!["permissions.allow"]);
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn host_removals_propagate() {
+        let base = json!({"a": 1, "b": 2});
+        let ours = json!({"a": 1, "b": 2, "c": 3});
+        let theirs = json!({"a": 1});
+
+        let (merged, report) = merge_json(Some(&base), &ours, &theirs);
+        assert_eq!(merged, json!({"a": 1, "c": 3}));
+        assert_eq!(report.updated, vec!["b"]);
+    }
+
+    #[test]
+    fn conflicting_json_edits_keep_container_value() {
+        let base = json!({"model": "sonnet"});
+        let ours = json!({"model": "opus"});
+        let theirs = json!({"model": "haiku"});
+
+        let (merged, report) = merge_json(Some(&base), &ours, &theirs);
+        assert_eq!(merged, ours);
+        assert_eq!(report.conflicts, vec!["model"]);
+    }
+
+    #[test]
+    fn missing_base_unions_keys() {
+        let ours = json!({"a": 1, "shared": "x"});
+        let theirs = json!({"b": 2, "shared": "x"});
+
+        let (merged, report) = merge_json(None, &ours, &theirs);
+        assert_eq!(merged, json!({"a": 1, "b": 2, "shared": "x"}));
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn unchanged_host_copy_is_a_no_op() {
+        let base = json!({"a": 1});
+        let ours = json!({"a": 2});
+        let (merged, report) = merge_json(Some(&base), &ours, &base);
+        assert_eq!(merged, ours);
+        assert_eq!(report, MergeReport::default());
+    }
+
+    #[test]
+    fn text_merge_combines_separate_edits() {
+        let base = "# Rules\n\none\n\ntwo\n\nthree\n";
+        let ours = "# Rules\n\none\n\ntwo\n\nthree\n\nremember: use pnpm\n";
+        let theirs = "# House rules\n\none\n\ntwo\n\nthree\n";
+
+        let (merged, report) = merge_text(Some(base), ours, theirs);
+        assert_eq!(
+            merged,
+            "# House rules\n\none\n\ntwo\n\nthree\n\nremember: use pnpm\n"
+        );
+        assert_eq!(report.updated, vec!["(text)"]);
+    }
+
+    #[test]
+    fn text_conflict_keeps_container_copy() {
+        let (merged, report) = merge_text(Some("a\n"), "b\n", "c\n");
+        assert_eq!(merged, "b\n");
+        assert_eq!(report.conflicts, vec!["(text)"]);
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+
+use crate::config::AppConfig;
+use crate::hardening::ResourceLimits;
+use crate::project::{NetworkMode, ProjectConfig};
+
+const CONTAINER_CLAUDE_MD: &str = r#"# Container Environment
+You are running inside a Podman container. To reach services on the host machine,
+use `host.containers.internal` instead of `localhost`.
+"#;
+
+const HOST_ACCESS_UNRESTRICTED_MD: &str = r#"
+For example: `curl http://host.containers.internal:3000`
+"#;
+
+const WORKDIR_MD: &str = "\nWorking directory: /app\n";
+
+/// Prints `<name>\t<first line of version output>` for every tool found on PATH.
+const TOOLCHAIN_PROBE: &str = r#"
+for t in node npm pnpm yarn bun deno python3 pip3 uv go rustc cargo java ruby php gcc make; do
+  command -v "$t" >/dev/null 2>&1 || continue
+  case "$t" in
+    go) v=$(go version 2>&1) ;;
+    java) v=$(java -version 2>&1) ;;
+    *) v=$("$t" --version 2>&1) ;;
+  esac
+  printf '%s\t%s\n' "$t" "$(printf '%s\n' "$v" | head -n 1)"
+done
+"#;
+
+/// A language runtime or build tool found in the image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Toolchain {
+    pub name: String,
+    pub version: String,
+}
+
+/// What the agent should know about the container it is launched into.
+pub struct EnvironmentContext<'a> {
+    pub project: &'a ProjectConfig,
+    /// `-p` specs published to the host.
+    pub ports: &'a [String],
+    pub read_only_workspace: bool,
+    pub sandboxed: bool,
+    pub toolchains: &'a [Toolchain],
+}
+
+/// First token of a version line that starts with a digit, e.g. `v20.11.0` -> `20.11.0`.
+fn extract_version(line: &str) -> Option<String> {
+    line.split(|c: char| c.is_whitespace() || c == '"' || c == ',')
+        .map(|token| token.trim_start_matches(['v', 'V']))
+        .map(|token| token.strip_prefix("go").unwrap_or(token))
+        .find(|token| token.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+fn parse_probe_output(output: &str) -> Vec<Toolchain> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line.split_once('\t')?;
+            let version = extract_version(rest).unwrap_or_else(|| rest.trim().to_string());
+            Some(Toolchain {
+                name: name.to_string(),
+                version,
+            })
+        })
+        .collect()
+}
+
+fn image_id(image: &str) -> Option<String> {
+    let output = Command::new("podman")
+        .args(["image", "inspect", "--format", "{{.Id}}", image])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !id.is_empty()).then_some(id)
+}
+
+fn probe_image(image: &str) -> Result<Vec<Toolchain>> {
+    let output = Command::new("podman")
+        .args([
+            "run",
+            "--rm",
+            "--network=none",
+            "--entrypoint",
+            "/bin/sh",
+            image,
+            "-c",
+            TOOLCHAIN_PROBE,
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to probe image for toolchains")?;
+    Ok(parse_probe_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Toolchains installed in `image`, probed once per image id and cached in
+/// `~/.ai-pod/toolchains.json`. Detection is best effort and yields an empty list on failure.
+pub fn detect_toolchains(config: &AppConfig, image: &str) -> Vec<Toolchain> {
+    let Some(id) = image_id(image) else {
+        return Vec::new();
+    };
+    let mut cache: BTreeMap<String, Vec<Toolchain>> =
+        std::fs::read_to_string(&config.toolchain_cache)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+    if let Some(toolchains) = cache.get(&id) {
+        return toolchains.clone();
+    }
+
+    let Ok(toolchains) = probe_image(image) else {
+        return Vec::new();
+    };
+    cache.insert(id, toolchains.clone());
+    if let Ok(raw) = serde_json::to_string_pretty(&cache) {
+        let _ = std::fs::write(&config.toolchain_cache, raw);
+    }
+    toolchains
+}
+
+/// Describes which host services the container can reach.
+fn host_access_md(project: &ProjectConfig) -> String {
+    let Some(services) = &project.host_access else {
+        return HOST_ACCESS_UNRESTRICTED_MD.to_string();
+    };
+    if services.is_empty() {
+        return "\nNo host services are reachable from this container.\n".to_string();
+    }
+
+    let mut md =
+        "\nOnly these host services are reachable; all other host ports are blocked:\n".to_string();
+    for service in services {
+        match service.description() {
+            Some(desc) => md.push_str(&format!(
+                "- `host.containers.internal:{}` — {}\n",
+                service.port(),
+                desc
+            )),
+            None => md.push_str(&format!(
+                "- `host.containers.internal:{}`\n",
+                service.port()
+            )),
+        }
+    }
+    md
+}
+
+/// Splits a `-p` spec (`[ip:]host:container[/proto]` or `container`) into
+/// (container port, host port).
+fn split_port_spec(spec: &str) -> (&str, &str) {
+    let spec = spec.split('/').next().unwrap_or(spec);
+    let mut parts = spec.rsplit(':');
+    let container = parts.next().unwrap_or(spec);
+    let host = parts.next().unwrap_or(container);
+    (container, host)
+}
+
+fn published_ports_md(ports: &[String]) -> String {
+    if ports.is_empty() {
+        return String::new();
+    }
+    let mut md = "\n## Published ports\nServers listening on `0.0.0.0` at these ports are reachable from the host:\n".to_string();
+    for spec in ports {
+        let (container, host) = split_port_spec(spec);
+        md.push_str(&format!(
+            "- container port {} → host port {}\n",
+            container, host
+        ));
+    }
+    md
+}
+
+fn network_md(project: &ProjectConfig) -> String {
+    let network = &project.network;
+    match network.mode {
+        NetworkMode::Full => String::new(),
+        NetworkMode::None => {
+            "\n## Network\nThe container has no network route; only the Anthropic API is reachable, \
+             through a proxy (`HTTPS_PROXY` is set). Package installs and downloads will fail.\n"
+                .to_string()
+        }
+        NetworkMode::Allowlist => {
+            let mut md = "\n## Network\nOutbound HTTP(S) goes through a proxy (`HTTPS_PROXY` is set) that only allows \
+                 the Anthropic API and:\n"
+                .to_string();
+            for entry in &network.allow {
+                md.push_str(&format!("- `{}`\n", entry));
+            }
+            md.push_str(
+                "Other destinations are refused with 403. Connections that bypass the proxy and direct DNS lookups fail.\n",
+            );
+            md
+        }
+    }
+}
+
+fn filesystem_md(ctx: &EnvironmentContext) -> String {
+    let mut lines = Vec::new();
+    if ctx.read_only_workspace {
+        lines.push("- `/app` is mounted read-only; you cannot modify the workspace.".to_string());
+    } else {
+        if ctx.sandboxed {
+            lines.push(
+                "- `/app` is a sandboxed copy of the workspace; the user reviews your changes before they are applied."
+                    .to_string(),
+            );
+        }
+        for path in &ctx.project.protected_paths {
+            lines.push(format!(
+                "- `/app/{}` is read-only.",
+                path.trim_end_matches('/')
+            ));
+        }
+    }
+    let limits = ResourceLimits::from_config(&ctx.project.hardening).ok();
+    if limits.as_ref().is_some_and(|l| l.read_only_root) {
+        lines.push(
+            "- The root filesystem is read-only; use `/tmp` or `/app` for scratch files."
+                .to_string(),
+        );
+    }
+    if let Some(limits) = limits {
+        lines.push(format!("- Resource limits: {}.", limits.summary()));
+    }
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!("\n## Constraints\n{}\n", lines.join("\n"))
+}
+
+fn toolchains_md(toolchains: &[Toolchain]) -> String {
+    if toolchains.is_empty() {
+        return String::new();
+    }
+    let mut md = "\n## Installed toolchains\n".to_string();
+    for t in toolchains {
+        md.push_str(&format!("- {} {}\n", t.name, t.version));
+    }
+    md
+}
+
+/// The container section at the top of the runtime CLAUDE.md.
+pub fn render(ctx: &EnvironmentContext) -> String {
+    let mut md = CONTAINER_CLAUDE_MD.to_string();
+    md.push_str(&host_access_md(ctx.project));
+    md.push_str(WORKDIR_MD);
+    md.push_str(&published_ports_md(ctx.ports));
+    md.push_str(&network_md(ctx.project));
+    md.push_str(&filesystem_md(ctx));
+    md.push_str(&toolchains_md(ctx.toolchains));
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx<'a>(project: &'a ProjectConfig, ports: &'a [String]) -> EnvironmentContext<'a> {
+        EnvironmentContext {
+            project,
+            ports,
+            read_only_workspace: false,
+            sandboxed: false,
+            toolchains: &[],
+        }
+    }
+
+    #[test]
+    fn parses_probe_output() {
+        let output = "node\tv20.11.0\npython3\tPython 3.12.1\ngo\tgo version go1.22.0 linux/amd64\n\
+                      java\topenjdk version \"21.0.2\" 2024-01-16\nmake\tGNU Make 4.3\n";
+        let toolchains = parse_probe_output(output);
+        let versions: Vec<(&str, &str)> = toolchains
+            .iter()
+            .map(|t| (t.name.as_str(), t.version.as_str()))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("node", "20.11.0"),
+                ("python3", "3.12.1"),
+                ("go", "1.22.0"),
+                ("java", "21.0.2"),
+                ("make", "4.3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn port_specs_are_split_into_container_and_host() {
+        assert_eq!(split_port_spec("8080:80"), ("80", "8080"));
+        assert_eq!(split_port_spec("127.0.0.1:8080:80/tcp"), ("80", "8080"));
+        assert_eq!(split_port_spec("3000"), ("3000", "3000"));
+    }
+
+    #[test]
+    fn default_preamble_has_no_restriction_sections() {
+        let project = ProjectConfig::default();
+        let md = render(&ctx(&project, &[]));
+        assert!(md.contains("Podman container"));
+        assert!(md.contains("curl http://host.containers.internal:3000"));
+        assert!(!md.contains("## Network"));
+        assert!(!md.contains("## Published ports"));
+        assert!(!md.contains("read-only"));
+    }
+
+    #[test]
+    fn preamble_describes_launch_context() {
+        let project = ProjectConfig::parse(
+            r#"
+            protected_paths = [".github/workflows/"]
+            [network]
+            mode = "allowlist"
+            allow = ["github.com"]
+            [hardening]
+            profile = "strict"
+            "#,
+        )
+        .unwrap();
+        let ports = vec!["8080:80".to_string()];
+        let toolchains = vec![Toolchain {
+            name: "node".into(),
+            version: "20.11.0".into(),
+        }];
+        let md = render(&EnvironmentContext {
+            sandboxed: true,
+            toolchains: &toolchains,
+            ..ctx(&project, &ports)
+        });
+
+        assert!(md.contains("container port 80 → host port 8080"));
+        assert!(md.contains("- `github.com`"));
+        assert!(md.contains("`/app/.github/workflows` is read-only"));
+        assert!(md.contains("sandboxed copy"));
+        assert!(md.contains("root filesystem is read-only"));
+        assert!(md.contains("- node 20.11.0"));
+    }
+
+    #[test]
+    fn read_only_workspace_replaces_protected_paths() {
+        let project = ProjectConfig::parse(r#"protected_paths = ["infra"]"#).unwrap();
+        let md = render(&EnvironmentContext {
+            read_only_workspace: true,
+            ..ctx(&project, &[])
+        });
+        assert!(md.contains("`/app` is mounted read-only"));
+        assert!(!md.contains("/app/infra"));
+    }
+}
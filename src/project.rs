@@ -0,0 +1,412 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const PROJECT_CONFIG_NAME: &str = "ai-pod.toml";
+/// Project notes appended to the container's CLAUDE.md after the generated preamble.
+pub const PROJECT_CLAUDE_MD_NAME: &str = "ai-pod.CLAUDE.md";
+
+/// Workspace files that set the container's policy. The agent can write to the
+/// workspace, so they are mounted read-only and changes between launches need
+/// confirmation on the host.
+pub const POLICY_FILES: &[&str] = &[PROJECT_CONFIG_NAME, PROJECT_CLAUDE_MD_NAME];
+
+/// Policy file contents per file name; `None` for a file that doesn't exist.
+type Policy = BTreeMap<String, Option<String>>;
+
+/// Per-workspace settings read from `ai-pod.toml` next to `ai-pod.Dockerfile`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub network: NetworkConfig,
+    /// Host ports reachable via `host.containers.internal`. `None` leaves host access
+    /// unrestricted; an empty list blocks everything except the notification server.
+    pub host_access: Option<Vec<HostService>>,
+    pub hardening: HardeningConfig,
+    /// Workspace-relative paths mounted read-only inside the container.
+    pub protected_paths: Vec<String>,
+    pub claude: ClaudeConfig,
+}
+
+/// A host port the container may reach, written either as a bare port number
+/// or as `{ port = 5432, description = "Postgres" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum HostService {
+    Port(u16),
+    Described {
+        port: u16,
+        description: Option<String>,
+    },
+}
+
+impl HostService {
+    pub fn port(&self) -> u16 {
+        match self {
+            Self::Port(port) | Self::Described { port, .. } => *port,
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Self::Port(_) => None,
+            Self::Described { description, .. } => description.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// Unrestricted egress (the default).
+    #[default]
+    Full,
+    /// No egress beyond what Claude itself needs.
+    None,
+    /// Egress only to the domains and CIDRs listed in `allow`.
+    Allowlist,
+}
+
+impl NetworkMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::None => "none",
+            Self::Allowlist => "allowlist",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub mode: NetworkMode,
+    /// Domains (matching subdomains too) and CIDR ranges, e.g. `github.com`, `10.0.0.0/8`.
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HardeningProfile {
+    /// Dropped capabilities, no-new-privileges and generous resource limits.
+    #[default]
+    Default,
+    /// Tighter limits and a read-only root filesystem.
+    Strict,
+    /// The default profile with the limits set in `[hardening]` applied on top.
+    Custom,
+}
+
+impl HardeningProfile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Strict => "strict",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+/// `[hardening]` section. The limit fields are only honoured by the `custom` profile.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HardeningConfig {
+    pub profile: HardeningProfile,
+    /// Memory limit in podman syntax, e.g. `4g`.
+    pub memory: Option<String>,
+    /// Number of CPUs, e.g. `2` or `1.5`.
+    pub cpus: Option<f64>,
+    pub pids_limit: Option<u32>,
+    pub read_only_root: Option<bool>,
+    /// Capabilities to add back after all are dropped.
+    pub cap_add: Vec<String>,
+}
+
+/// `[claude]` section: additions to Claude's settings for this project.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClaudeConfig {
+    /// Environment variables set through `settings.json`.
+    pub env: BTreeMap<String, String>,
+    pub permissions: PermissionsConfig,
+    /// MCP server definitions added to `~/.claude.json`, in Claude's own format.
+    pub mcp_servers: BTreeMap<String, serde_json::Value>,
+}
+
+/// Permission rules appended to `permissions.allow` / `permissions.deny`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PermissionsConfig {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl ProjectConfig {
+    /// Loads `ai-pod.toml` from the workspace, falling back to defaults if it does not exist.
+    pub fn load(workspace: &Path) -> Result<Self> {
+        let path = workspace.join(PROJECT_CONFIG_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&raw).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+}
+
+/// Reads the workspace's `ai-pod.CLAUDE.md` fragment, if there is one.
+pub fn load_claude_md_fragment(workspace: &Path) -> Result<Option<String>> {
+    let path = workspace.join(PROJECT_CLAUDE_MD_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(&path)
+        .map(Some)
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn read_policy(workspace: &Path) -> Policy {
+    POLICY_FILES
+        .iter()
+        .map(|name| {
+            let content = std::fs::read_to_string(workspace.join(name)).ok();
+            (name.to_string(), content)
+        })
+        .collect()
+}
+
+/// Files whose content differs from the trusted policy, as (name, trusted, current).
+fn policy_changes<'a>(
+    trusted: &'a Policy,
+    current: &'a Policy,
+) -> Vec<(&'a str, Option<&'a str>, Option<&'a str>)> {
+    current
+        .iter()
+        .filter_map(|(name, content)| {
+            let before = trusted.get(name).and_then(|c| c.as_deref());
+            (before != content.as_deref()).then_some((name.as_str(), before, content.as_deref()))
+        })
+        .collect()
+}
+
+/// Asks before launching with policy files that changed since the last launch of the
+/// workspace, e.g. because the agent rewrote them. The policy seen on a workspace's
+/// first launch is trusted as is. Returns `false` if the user declines. A trust file that
+/// can't be parsed is an error rather than a fresh start, which would trust every policy.
+pub fn confirm_policy(trust_file: &Path, workspace: &Path) -> Result<bool> {
+    let mut trusted: BTreeMap<String, Policy> = match std::fs::read_to_string(trust_file) {
+        Ok(raw) => serde_json::from_str(&raw).with_context(|| {
+            format!(
+                "Failed to parse {}; fix or remove it to trust the current policies",
+                trust_file.display()
+            )
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", trust_file.display()));
+        }
+    };
+    let key = workspace.to_string_lossy().into_owned();
+    let current = read_policy(workspace);
+
+    if let Some(known) = trusted.get(&key) {
+        let changes = policy_changes(known, &current);
+        if changes.is_empty() {
+            return Ok(true);
+        }
+        println!(
+            "\n{}",
+            "⚠  The project policy changed since the last launch:"
+                .yellow()
+                .bold()
+        );
+        for (name, before, after) in changes {
+            println!("\n{}", name.bold());
+            let patch = diffy::create_patch(before.unwrap_or(""), after.unwrap_or(""));
+            print!("{}", patch);
+        }
+        println!(
+            "\n{}",
+            "The workspace is writable from the container; make sure you made these changes."
+                .yellow()
+        );
+        let proceed = dialoguer::Confirm::new()
+            .with_prompt("Launch with the changed policy?")
+            .default(false)
+            .interact()?;
+        if !proceed {
+            return Ok(false);
+        }
+    }
+
+    trusted.insert(key, current);
+    std::fs::write(trust_file, serde_json::to_string_pretty(&trusted)?)
+        .with_context(|| format!("Failed to write {}", trust_file.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn missing_file_yields_defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = ProjectConfig::load(dir.path()).unwrap();
+        assert_eq!(config.network.mode, NetworkMode::Full);
+        assert!(config.network.allow.is_empty());
+    }
+
+    #[test]
+    fn parses_network_allowlist() {
+        let config = ProjectConfig::parse(
+            r#"
+            [network]
+            mode = "allowlist"
+            allow = ["github.com", "10.0.0.0/8"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.network.mode, NetworkMode::Allowlist);
+        assert_eq!(config.network.allow, vec!["github.com", "10.0.0.0/8"]);
+    }
+
+    #[test]
+    fn host_access_defaults_to_unrestricted() {
+        let config = ProjectConfig::parse("").unwrap();
+        assert!(config.host_access.is_none());
+    }
+
+    #[test]
+    fn parses_host_access_ports_and_services() {
+        let config = ProjectConfig::parse(
+            r#"host_access = [3000, { port = 5432, description = "Postgres" }]"#,
+        )
+        .unwrap();
+        let services = config.host_access.unwrap();
+        assert_eq!(services[0].port(), 3000);
+        assert_eq!(services[0].description(), None);
+        assert_eq!(services[1].port(), 5432);
+        assert_eq!(services[1].description(), Some("Postgres"));
+    }
+
+    #[test]
+    fn parses_custom_hardening() {
+        let config = ProjectConfig::parse(
+            r#"
+            [hardening]
+            profile = "custom"
+            memory = "2g"
+            cpus = 1.5
+            pids_limit = 512
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.hardening.profile, HardeningProfile::Custom);
+        assert_eq!(config.hardening.memory.as_deref(), Some("2g"));
+        assert_eq!(config.hardening.cpus, Some(1.5));
+        assert_eq!(config.hardening.pids_limit, Some(512));
+    }
+
+    #[test]
+    fn parses_protected_paths() {
+        let config =
+            ProjectConfig::parse(r#"protected_paths = [".git/hooks", ".github/workflows"]"#)
+                .unwrap();
+        assert_eq!(config.protected_paths, vec![".git/hooks", ".github/workflows"]);
+    }
+
+    #[test]
+    fn claude_md_fragment_is_optional() {
+        let dir = TempDir::new().unwrap();
+        assert!(load_claude_md_fragment(dir.path()).unwrap().is_none());
+        std::fs::write(dir.path().join(PROJECT_CLAUDE_MD_NAME), "notes").unwrap();
+        assert_eq!(load_claude_md_fragment(dir.path()).unwrap().as_deref(), Some("notes"));
+    }
+
+    #[test]
+    fn policy_is_trusted_on_first_launch_and_changes_are_detected() {
+        let dir = TempDir::new().unwrap();
+        let trust_file = dir.path().join("trusted-policy.json");
+        let workspace = dir.path().join("app");
+        std::fs::create_dir(&workspace).unwrap();
+        std::fs::write(
+            workspace.join(PROJECT_CONFIG_NAME),
+            "[network]\nmode = \"none\"\n",
+        )
+        .unwrap();
+
+        // Unchanged policies don't prompt.
+        assert!(confirm_policy(&trust_file, &workspace).unwrap());
+        assert!(confirm_policy(&trust_file, &workspace).unwrap());
+
+        let trusted = read_policy(&workspace);
+        std::fs::write(workspace.join(PROJECT_CONFIG_NAME), "").unwrap();
+        std::fs::write(workspace.join(PROJECT_CLAUDE_MD_NAME), "notes").unwrap();
+        let current = read_policy(&workspace);
+        let changes = policy_changes(&trusted, &current);
+        assert_eq!(
+            changes,
+            vec![
+                (PROJECT_CLAUDE_MD_NAME, None, Some("notes")),
+                (
+                    PROJECT_CONFIG_NAME,
+                    Some("[network]\nmode = \"none\"\n"),
+                    Some("")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupt_trust_file_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let trust_file = dir.path().join("trusted-policy.json");
+        std::fs::write(&trust_file, "{\"/src/app\": {").unwrap();
+        assert!(confirm_policy(&trust_file, dir.path()).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&trust_file).unwrap(),
+            "{\"/src/app\": {"
+        );
+    }
+
+    #[test]
+    fn parses_claude_section() {
+        let config = ProjectConfig::parse(
+            r#"
+            [claude.env]
+            RUST_LOG = "debug"
+
+            [claude.permissions]
+            allow = ["Bash(cargo test:*)"]
+
+            [claude.mcp_servers.postgres]
+            command = "npx"
+            args = ["-y", "server-postgres"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.claude.env["RUST_LOG"], "debug");
+        assert_eq!(config.claude.permissions.allow, vec!["Bash(cargo test:*)"]);
+        assert!(config.claude.permissions.deny.is_empty());
+        assert_eq!(config.claude.mcp_servers["postgres"]["command"], "npx");
+        assert_eq!(config.claude.mcp_servers["postgres"]["args"][1], "server-postgres");
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(ProjectConfig::parse("[network]\nmodes = \"none\"").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_network_mode() {
+        assert!(ProjectConfig::parse("[network]\nmode = \"partial\"").is_err());
+    }
+}
@@ -0,0 +1,540 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use walkdir::WalkDir;
+
+use crate::config::AppConfig;
+
+/// Where a sandbox keeps the agent's changes.
+#[derive(Debug)]
+pub enum SandboxKind {
+    /// A detached git worktree of the workspace repository, checked out at `base`.
+    /// `admin_dir` is the worktree's own directory inside the shared `git_dir`; objects
+    /// the agent writes land in an overlay under `objects`.
+    Worktree {
+        base: String,
+        git_dir: PathBuf,
+        admin_dir: PathBuf,
+        objects: PathBuf,
+    },
+    /// A copy-on-write overlay over the workspace; changes land in `upper`.
+    Overlay { upper: PathBuf, work: PathBuf },
+}
+
+/// An isolated copy of the workspace mounted as `/app` instead of the live directory.
+#[derive(Debug)]
+pub struct Sandbox {
+    /// Session the sandbox container runs as: the `--session` name or a random id.
+    pub session: String,
+    workspace: PathBuf,
+    dir: PathBuf,
+    kind: SandboxKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct OverlayChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Builds a command that runs inside podman's user namespace when rootless, so files
+/// written by the container user can be read, changed and removed from the host.
+fn host_command(program: &str) -> Command {
+    if unsafe { libc::geteuid() } == 0 {
+        return Command::new(program);
+    }
+    let mut cmd = Command::new("podman");
+    cmd.args(["unshare", program]);
+    cmd
+}
+
+fn git_output(workspace: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(workspace)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns true if `workspace` is the top level of a git repository with at least one commit.
+pub fn is_git_toplevel(workspace: &Path) -> bool {
+    let toplevel = git_output(workspace, &["rev-parse", "--show-toplevel"]);
+    let has_head = git_output(workspace, &["rev-parse", "--verify", "HEAD"]).is_some();
+    has_head
+        && toplevel.is_some_and(|t| {
+            std::fs::canonicalize(t).ok() == std::fs::canonicalize(workspace).ok()
+        })
+}
+
+/// Lists the changes recorded in an overlay upper directory relative to `lower`.
+/// Whiteouts (0:0 character devices) mark deleted files.
+pub fn overlay_changes(upper: &Path, lower: &Path) -> Vec<OverlayChange> {
+    let mut changes: Vec<OverlayChange> = WalkDir::new(upper)
+        .min_depth(1)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(upper).ok()?.to_path_buf();
+            let file_type = e.file_type();
+            let kind = if file_type.is_char_device() && e.metadata().ok()?.rdev() == 0 {
+                ChangeKind::Deleted
+            } else if file_type.is_file() || file_type.is_symlink() {
+                if lower.join(&relative).exists() {
+                    ChangeKind::Modified
+                } else {
+                    ChangeKind::Added
+                }
+            } else {
+                return None;
+            };
+            Some(OverlayChange {
+                path: relative,
+                kind,
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+fn apply_overlay_changes(upper: &Path, workspace: &Path, changes: &[OverlayChange]) -> Result<()> {
+    for change in changes {
+        let target = workspace.join(&change.path);
+        match change.kind {
+            ChangeKind::Deleted => {
+                if target.is_dir() {
+                    std::fs::remove_dir_all(&target)?;
+                } else if target.exists() {
+                    std::fs::remove_file(&target)?;
+                }
+            }
+            ChangeKind::Added | ChangeKind::Modified => {
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(upper.join(&change.path), &target)
+                    .with_context(|| format!("Failed to apply {}", change.path.display()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Sandbox {
+    /// Creates a sandbox for `workspace`: a git worktree if the workspace is a repository
+    /// root, otherwise an overlay. Uncommitted changes to tracked files are carried over.
+    pub fn create(config: &AppConfig, workspace: &Path, session: Option<&str>) -> Result<Self> {
+        let session = session
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()[..6].to_string());
+        let container_name = crate::container::session_container_name(workspace, Some(&session));
+        let dir = config.sandbox_dir.join(&container_name);
+        if dir.exists() {
+            anyhow::bail!(
+                "A sandbox for session '{}' already exists at {}",
+                session,
+                dir.display()
+            );
+        }
+        std::fs::create_dir_all(&config.sandbox_dir).context("Failed to create sandbox dir")?;
+
+        let kind = if is_git_toplevel(workspace) {
+            // `git stash create` snapshots the working tree without touching it;
+            // it prints nothing when there are no local changes.
+            let base = git_output(workspace, &["stash", "create"])
+                .filter(|s| !s.is_empty())
+                .or_else(|| git_output(workspace, &["rev-parse", "HEAD"]))
+                .context("Failed to resolve sandbox base commit")?;
+            let git_dir = git_output(
+                workspace,
+                &["rev-parse", "--path-format=absolute", "--git-common-dir"],
+            )
+            .context("Failed to locate git directory")?;
+
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(workspace)
+                .args(["worktree", "add", "--detach"])
+                .arg(&dir)
+                .arg(&base)
+                .stdout(Stdio::null())
+                .status()
+                .context("Failed to run git worktree")?;
+            if !status.success() {
+                anyhow::bail!("git worktree add failed");
+            }
+            let admin_dir = git_output(&dir, &["rev-parse", "--path-format=absolute", "--git-dir"])
+                .context("Failed to locate sandbox worktree")?;
+            let objects = config
+                .sandbox_dir
+                .join(format!("{}.objects", container_name));
+            std::fs::create_dir_all(objects.join("upper"))?;
+            std::fs::create_dir_all(objects.join("work"))?;
+            SandboxKind::Worktree {
+                base,
+                git_dir: PathBuf::from(git_dir),
+                admin_dir: PathBuf::from(admin_dir),
+                objects,
+            }
+        } else {
+            let upper = dir.join("upper");
+            let work = dir.join("work");
+            std::fs::create_dir_all(&upper)?;
+            std::fs::create_dir_all(&work)?;
+            SandboxKind::Overlay { upper, work }
+        };
+
+        let label = match kind {
+            SandboxKind::Worktree { .. } => "git worktree",
+            SandboxKind::Overlay { .. } => "overlay",
+        };
+        println!(
+            "{} {} ({}, session {})",
+            "Sandbox:".blue().bold(),
+            dir.display(),
+            label,
+            session
+        );
+
+        Ok(Self {
+            session,
+            workspace: workspace.to_path_buf(),
+            dir,
+            kind,
+        })
+    }
+
+    /// Host directory whose contents appear at `/app`.
+    pub fn root(&self) -> &Path {
+        match self.kind {
+            SandboxKind::Worktree { .. } => &self.dir,
+            SandboxKind::Overlay { .. } => &self.workspace,
+        }
+    }
+
+    /// True if the sandbox's `.git` is mounted read-only already, so protected paths
+    /// inside it need no mounts of their own.
+    pub fn protects_git_dir(&self) -> bool {
+        matches!(self.kind, SandboxKind::Worktree { .. })
+    }
+
+    /// `podman run` mount arguments for `/app` and anything the sandbox depends on.
+    pub fn mount_args(&self) -> Vec<String> {
+        match &self.kind {
+            // The worktree's `.git` file points into the main repository's git dir, so
+            // that is mounted at the same absolute path. It is shared with the host, so
+            // only the worktree's own state is writable; new objects go to an overlay.
+            SandboxKind::Worktree {
+                git_dir,
+                admin_dir,
+                objects,
+                ..
+            } => vec![
+                "-v".into(),
+                format!("{}:/app:Z", self.dir.to_string_lossy()),
+                "-v".into(),
+                format!("{0}:{0}:ro,z", git_dir.to_string_lossy()),
+                "-v".into(),
+                format!("{0}:{0}:z", admin_dir.to_string_lossy()),
+                "-v".into(),
+                format!(
+                    "{0}:{0}:O,upperdir={1},workdir={2}",
+                    git_dir.join("objects").to_string_lossy(),
+                    objects.join("upper").to_string_lossy(),
+                    objects.join("work").to_string_lossy()
+                ),
+            ],
+            SandboxKind::Overlay { upper, work } => vec![
+                "-v".into(),
+                format!(
+                    "{}:/app:O,upperdir={},workdir={}",
+                    self.workspace.to_string_lossy(),
+                    upper.to_string_lossy(),
+                    work.to_string_lossy()
+                ),
+            ],
+        }
+    }
+
+    /// Runs `git diff --cached <base>` in the worktree against a throwaway index that
+    /// holds every file, so untracked files and the agent's own commits are included.
+    /// The objects the agent wrote are read from the sandbox's overlay.
+    fn worktree_diff(&self, base: &str, objects: &Path, extra: &[&str]) -> Result<Vec<u8>> {
+        let index_dir = tempfile::TempDir::new()?;
+        let index = index_dir.path().join("index");
+        let alternates = objects.join("upper");
+
+        let status = host_command("git")
+            .env("GIT_INDEX_FILE", &index)
+            .env("GIT_ALTERNATE_OBJECT_DIRECTORIES", &alternates)
+            .arg("-C")
+            .arg(&self.dir)
+            .args(["add", "-A"])
+            .status()
+            .context("Failed to stage sandbox changes")?;
+        if !status.success() {
+            anyhow::bail!("git add failed in sandbox");
+        }
+
+        let output = host_command("git")
+            .env("GIT_INDEX_FILE", &index)
+            .env("GIT_ALTERNATE_OBJECT_DIRECTORIES", &alternates)
+            .arg("-C")
+            .arg(&self.dir)
+            .args(["diff", "--cached"])
+            .args(extra)
+            .arg(base)
+            .output()
+            .context("Failed to diff sandbox")?;
+        if !output.status.success() {
+            anyhow::bail!("git diff failed in sandbox");
+        }
+        Ok(output.stdout)
+    }
+
+    /// Human-readable summary of the changes made in the sandbox; empty if there are none.
+    pub fn diff_summary(&self) -> Result<String> {
+        match &self.kind {
+            SandboxKind::Worktree { base, objects, .. } => {
+                let stat = self.worktree_diff(base, objects, &["--stat"])?;
+                Ok(String::from_utf8_lossy(&stat).trim_end().to_string())
+            }
+            SandboxKind::Overlay { upper, .. } => Ok(overlay_changes(upper, &self.workspace)
+                .iter()
+                .map(|c| {
+                    let marker = match c.kind {
+                        ChangeKind::Added => "A",
+                        ChangeKind::Modified => "M",
+                        ChangeKind::Deleted => "D",
+                    };
+                    format!(" {} {}", marker, c.path.display())
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+        }
+    }
+
+    /// Applies the sandbox's changes to the live workspace.
+    pub fn apply(&self) -> Result<()> {
+        match &self.kind {
+            SandboxKind::Worktree { base, objects, .. } => {
+                let patch = self.worktree_diff(base, objects, &["--binary"])?;
+                let mut child = Command::new("git")
+                    .arg("-C")
+                    .arg(&self.workspace)
+                    .args(["apply", "--whitespace=nowarn", "-"])
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .context("Failed to run git apply")?;
+                {
+                    use std::io::Write;
+                    let mut stdin = child.stdin.take().context("Failed to open git apply stdin")?;
+                    stdin.write_all(&patch)?;
+                }
+                if !child.wait()?.success() {
+                    anyhow::bail!("git apply failed; the patch does not apply cleanly");
+                }
+            }
+            SandboxKind::Overlay { upper, .. } => {
+                let changes = overlay_changes(upper, &self.workspace);
+                apply_overlay_changes(upper, &self.workspace, &changes)?;
+            }
+        }
+        println!("{}", "Sandbox changes applied to workspace.".green());
+        Ok(())
+    }
+
+    /// Removes the sandbox and, for worktrees, its registration in the repository.
+    pub fn discard(&self) -> Result<()> {
+        if let SandboxKind::Worktree { .. } = self.kind {
+            let _ = host_command("git")
+                .arg("-C")
+                .arg(&self.workspace)
+                .args(["worktree", "remove", "--force"])
+                .arg(&self.dir)
+                .stderr(Stdio::null())
+                .status();
+        }
+        let mut dirs = vec![&self.dir];
+        if let SandboxKind::Worktree { objects, .. } = &self.kind {
+            dirs.push(objects);
+        }
+        for dir in dirs.into_iter().filter(|d| d.exists()) {
+            let status = host_command("rm")
+                .arg("-rf")
+                .arg(dir)
+                .status()
+                .context("Failed to remove sandbox")?;
+            if !status.success() {
+                anyhow::bail!("Failed to remove sandbox {}", dir.display());
+            }
+        }
+        if let SandboxKind::Worktree { .. } = self.kind {
+            let _ = Command::new("git")
+                .arg("-C")
+                .arg(&self.workspace)
+                .args(["worktree", "prune"])
+                .status();
+        }
+        println!("{}", "Sandbox discarded.".green());
+        Ok(())
+    }
+
+    /// Leaves the sandbox in place for the user to review later.
+    pub fn keep(&self) {
+        println!("{} {}", "Sandbox kept at".green(), self.dir.display());
+    }
+
+    /// Shows what the agent changed and asks whether to apply, keep or discard it.
+    pub fn finish(&self) -> Result<()> {
+        let summary = self.diff_summary()?;
+        if summary.is_empty() {
+            println!("{}", "No changes in sandbox.".blue());
+            return self.discard();
+        }
+
+        println!("\n{}", "Sandbox changes:".blue().bold());
+        println!("{}", summary);
+
+        let choice = dialoguer::Select::new()
+            .with_prompt("What should happen to these changes?")
+            .items(&["Apply to workspace", "Keep sandbox", "Discard"])
+            .default(0)
+            .interact()?;
+        match choice {
+            0 => match self.apply() {
+                Ok(()) => self.discard(),
+                Err(e) => {
+                    println!(
+                        "{} {e:#}\nSandbox kept at {}",
+                        "Failed to apply changes:".red(),
+                        self.dir.display()
+                    );
+                    Ok(())
+                }
+            },
+            1 => {
+                self.keep();
+                Ok(())
+            }
+            _ => self.discard(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn overlay_changes_detects_added_and_modified_files() {
+        let lower = TempDir::new().unwrap();
+        let upper = TempDir::new().unwrap();
+        std::fs::write(lower.path().join("a.txt"), "old").unwrap();
+        std::fs::write(upper.path().join("a.txt"), "new").unwrap();
+        std::fs::create_dir(upper.path().join("src")).unwrap();
+        std::fs::write(upper.path().join("src").join("b.rs"), "fn b() {}").unwrap();
+
+        let changes = overlay_changes(upper.path(), lower.path());
+        assert_eq!(
+            changes,
+            vec![
+                OverlayChange {
+                    path: PathBuf::from("a.txt"),
+                    kind: ChangeKind::Modified
+                },
+                OverlayChange {
+                    path: PathBuf::from("src/b.rs"),
+                    kind: ChangeKind::Added
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_overlay_has_no_changes() {
+        let lower = TempDir::new().unwrap();
+        let upper = TempDir::new().unwrap();
+        assert!(overlay_changes(upper.path(), lower.path()).is_empty());
+    }
+
+    #[test]
+    fn apply_overlay_changes_copies_and_deletes() {
+        let workspace = TempDir::new().unwrap();
+        let upper = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("gone.txt"), "bye").unwrap();
+        std::fs::create_dir(upper.path().join("new")).unwrap();
+        std::fs::write(upper.path().join("new").join("file.txt"), "hi").unwrap();
+
+        let changes = vec![
+            OverlayChange {
+                path: PathBuf::from("gone.txt"),
+                kind: ChangeKind::Deleted,
+            },
+            OverlayChange {
+                path: PathBuf::from("new/file.txt"),
+                kind: ChangeKind::Added,
+            },
+        ];
+        apply_overlay_changes(upper.path(), workspace.path(), &changes).unwrap();
+
+        assert!(!workspace.path().join("gone.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("new").join("file.txt")).unwrap(),
+            "hi"
+        );
+    }
+
+    #[test]
+    fn worktree_mounts_keep_the_shared_git_dir_read_only() {
+        let sandbox = Sandbox {
+            session: "a1".into(),
+            workspace: PathBuf::from("/src/app"),
+            dir: PathBuf::from("/sb/claude-app-a1"),
+            kind: SandboxKind::Worktree {
+                base: "HEAD".into(),
+                git_dir: PathBuf::from("/src/app/.git"),
+                admin_dir: PathBuf::from("/src/app/.git/worktrees/claude-app-a1"),
+                objects: PathBuf::from("/sb/claude-app-a1.objects"),
+            },
+        };
+        assert_eq!(
+            sandbox.mount_args(),
+            vec![
+                "-v",
+                "/sb/claude-app-a1:/app:Z",
+                "-v",
+                "/src/app/.git:/src/app/.git:ro,z",
+                "-v",
+                "/src/app/.git/worktrees/claude-app-a1:/src/app/.git/worktrees/claude-app-a1:z",
+                "-v",
+                "/src/app/.git/objects:/src/app/.git/objects:O,\
+                 upperdir=/sb/claude-app-a1.objects/upper,workdir=/sb/claude-app-a1.objects/work",
+            ]
+        );
+        assert!(sandbox.protects_git_dir());
+    }
+
+    #[test]
+    fn plain_directory_is_not_a_git_toplevel() {
+        let dir = TempDir::new().unwrap();
+        assert!(!is_git_toplevel(dir.path()));
+    }
+}
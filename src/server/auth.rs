@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use super::focus::Terminal;
+
+/// The container session a token was issued to. Events authenticated with the token
+/// are attributed to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenOwner {
+    pub project: String,
+    pub container: String,
+    pub workspace: String,
+    pub session: String,
+    /// When the container was started.
+    #[serde(default)]
+    pub started: Option<DateTime<Utc>>,
+    /// Terminal `ai-pod` was launched from.
+    #[serde(default)]
+    pub terminal: Terminal,
+    /// Entry in `history.jsonl` the session's events are counted for.
+    #[serde(default)]
+    pub history_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenFile {
+    token: String,
+    owner: TokenOwner,
+}
+
+fn random_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Writes `contents` to a new file readable only by the user.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+fn read_tokens(dir: &Path) -> Vec<(PathBuf, TokenFile)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let raw = std::fs::read_to_string(entry.path()).ok()?;
+            Some((entry.path(), serde_json::from_str(&raw).ok()?))
+        })
+        .collect()
+}
+
+/// Issues a token for a container about to start. It stays valid while the container
+/// exists, including after `ai-pod` detaches from it, and replaces any token left from
+/// an earlier container of the same name.
+pub fn issue(dir: &Path, owner: TokenOwner) -> Result<String> {
+    std::fs::create_dir_all(dir).context("Failed to create token directory")?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    revoke(dir, &owner.container);
+
+    let token = random_token();
+    let path = dir.join(format!("{}-{}.json", owner.container, &token[..8]));
+    let file = TokenFile {
+        token: token.clone(),
+        owner,
+    };
+    write_private(&path, &serde_json::to_string(&file)?)?;
+    Ok(token)
+}
+
+/// Revokes the tokens of `container`, once it has been removed.
+pub fn revoke(dir: &Path, container: &str) {
+    for (path, file) in read_tokens(dir) {
+        if file.owner.container == container {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Compares without exiting early, so response timing doesn't reveal a token prefix.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// The session that owns `token`, if it is still valid.
+pub fn find_owner(dir: &Path, token: &str) -> Option<TokenOwner> {
+    read_tokens(dir)
+        .into_iter()
+        .find(|(_, file)| constant_time_eq(&file.token, token))
+        .map(|(_, file)| file.owner)
+}
+
+/// Cookie the dashboard's browser session carries the host token in.
+pub const COOKIE: &str = "ai_pod_token";
+
+/// Token for host-side clients (`ai-pod events`, the dashboard), created on first use.
+/// An empty token file, e.g. one truncated by a crash, is replaced.
+pub fn host_token(path: &Path) -> Result<String> {
+    if let Ok(token) = std::fs::read_to_string(path) {
+        let token = token.trim();
+        if !token.is_empty() {
+            return Ok(token.to_string());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+    }
+    let token = random_token();
+    write_private(path, &token)?;
+    Ok(token)
+}
+
+/// Token from an `Authorization: Bearer` header.
+pub fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Token from the dashboard's cookie.
+fn cookie_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(axum::http::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(COOKIE)?.strip_prefix('='))
+}
+
+/// Token from a `token=` query parameter, for browsers opening the dashboard link. It is
+/// only traded for the cookie, so it doesn't stay in the address bar.
+pub fn query_token(query: Option<&str>) -> Option<&str> {
+    query?.split('&').find_map(|kv| kv.strip_prefix("token="))
+}
+
+/// `query` without its `token=` parameter.
+pub fn without_token(query: Option<&str>) -> String {
+    let rest: Vec<&str> = query
+        .unwrap_or("")
+        .split('&')
+        .filter(|kv| !kv.is_empty() && !kv.starts_with("token="))
+        .collect();
+    rest.join("&")
+}
+
+/// `Set-Cookie` value holding the host token for the dashboard.
+pub fn host_cookie(token: &str) -> String {
+    format!("{}={}; Path=/; HttpOnly; SameSite=Strict", COOKIE, token)
+}
+
+/// Whether `token` is the host token. Empty tokens never match.
+pub fn is_host_token(token: &str, host_token: &str) -> bool {
+    !host_token.is_empty() && constant_time_eq(token, host_token)
+}
+
+/// Whether the request carries the host token in a header or the dashboard's cookie.
+pub fn is_host(headers: &HeaderMap, host_token: &str) -> bool {
+    bearer(headers)
+        .or_else(|| cookie_token(headers))
+        .is_some_and(|token| is_host_token(token, host_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn owner(container: &str) -> TokenOwner {
+        TokenOwner {
+            project: "app".into(),
+            container: container.into(),
+            workspace: "/home/me/app".into(),
+            session: "default".into(),
+            started: None,
+            terminal: Terminal::default(),
+            history_id: None,
+        }
+    }
+
+    #[test]
+    fn issued_tokens_identify_their_session_until_revoked() {
+        let dir = TempDir::new().unwrap();
+        let tokens = dir.path().join("tokens");
+        let a = issue(&tokens, owner("claude-a")).unwrap();
+        let b = issue(&tokens, owner("claude-b")).unwrap();
+
+        assert_eq!(find_owner(&tokens, &a).unwrap().container, "claude-a");
+        assert_eq!(find_owner(&tokens, &b).unwrap().container, "claude-b");
+        assert!(find_owner(&tokens, "guess").is_none());
+
+        revoke(&tokens, "claude-a");
+        assert!(find_owner(&tokens, &a).is_none());
+        assert!(find_owner(&tokens, &b).is_some());
+    }
+
+    #[test]
+    fn a_new_container_replaces_the_token_of_its_predecessor() {
+        let dir = TempDir::new().unwrap();
+        let old = issue(dir.path(), owner("claude-a")).unwrap();
+        let new = issue(dir.path(), owner("claude-a")).unwrap();
+        assert!(find_owner(dir.path(), &old).is_none());
+        assert!(find_owner(dir.path(), &new).is_some());
+        assert_eq!(read_tokens(dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn host_token_is_stable_and_private() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.token");
+        let token = host_token(&path).unwrap();
+        assert_eq!(host_token(&path).unwrap(), token);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn truncated_host_token_is_replaced() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.token");
+        std::fs::write(&path, "\n").unwrap();
+        let token = host_token(&path).unwrap();
+        assert!(!token.is_empty());
+        assert_eq!(host_token(&path).unwrap(), token);
+    }
+
+    #[test]
+    fn host_token_is_accepted_from_header_or_cookie() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_host(&headers, "abc"));
+
+        headers.insert("cookie", "theme=dark; ai_pod_token=abc".parse().unwrap());
+        assert!(is_host(&headers, "abc"));
+        assert!(!is_host(&headers, "abd"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer abc".parse().unwrap());
+        assert!(is_host(&headers, "abc"));
+    }
+
+    #[test]
+    fn empty_tokens_are_rejected() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer ".parse().unwrap());
+        assert!(!is_host(&headers, ""));
+        assert!(!is_host_token("", ""));
+    }
+
+    #[test]
+    fn query_tokens_are_stripped_for_the_redirect() {
+        assert_eq!(query_token(Some("since=1&token=abc")), Some("abc"));
+        assert_eq!(without_token(Some("since=1&token=abc")), "since=1");
+        assert_eq!(without_token(Some("token=abc")), "");
+        assert_eq!(without_token(None), "");
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::os::fd::FromRawFd;
+use std::path::Path;
+
+use crate::user_config::ServerConfig;
+
+/// Interface name prefixes of the bridges Podman and Docker attach containers to.
+/// Rootless Podman (pasta, slirp4netns) and Podman machine forward
+/// `host.containers.internal` to loopback instead.
+const BRIDGE_PREFIXES: &[&str] = &["podman", "cni-podman", "docker", "br-"];
+
+/// First file descriptor passed by systemd socket activation.
+const LISTEN_FDS_START: i32 = 3;
+
+/// What the running server listens on, written to `~/.ai-pod/server.json` for
+/// `server-status` and for launches that need its port.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerState {
+    pub pid: u32,
+    /// Differs from `--notify-port` when that was taken by another program.
+    pub port: u16,
+    pub addrs: Vec<SocketAddr>,
+}
+
+/// IPv4 addresses of all network interfaces, with interface names.
+fn interface_addrs() -> Vec<(String, IpAddr)> {
+    let mut addrs = Vec::new();
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs allocates a linked list that is only read here and freed below.
+    unsafe {
+        if libc::getifaddrs(&mut ifap) != 0 {
+            return addrs;
+        }
+        let mut cur = ifap;
+        while !cur.is_null() {
+            let ifa = &*cur;
+            if !ifa.ifa_addr.is_null() && i32::from((*ifa.ifa_addr).sa_family) == libc::AF_INET {
+                let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+                let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+                addrs.push((name, IpAddr::V4(ip)));
+            }
+            cur = ifa.ifa_next;
+        }
+        libc::freeifaddrs(ifap);
+    }
+    addrs
+}
+
+/// Loopback plus the addresses of container bridge interfaces.
+fn select_addrs(interfaces: &[(String, IpAddr)]) -> Vec<IpAddr> {
+    let mut addrs = vec![IpAddr::V4(Ipv4Addr::LOCALHOST)];
+    for (name, ip) in interfaces {
+        if BRIDGE_PREFIXES.iter().any(|p| name.starts_with(p)) && !addrs.contains(ip) {
+            addrs.push(*ip);
+        }
+    }
+    addrs
+}
+
+/// Addresses the server should listen on: the configured ones, or loopback plus the
+/// container bridges found on this host.
+pub fn bind_addrs(config: &ServerConfig) -> Vec<IpAddr> {
+    match &config.bind {
+        Some(addrs) if !addrs.is_empty() => addrs.clone(),
+        _ => select_addrs(&interface_addrs()),
+    }
+}
+
+/// Listening sockets passed by systemd socket activation, if this process was started
+/// that way (`LISTEN_PID` is ours).
+pub fn inherited_listeners() -> Vec<TcpListener> {
+    let var = |key| std::env::var(key).ok().and_then(|v| v.parse::<u32>().ok());
+    if var("LISTEN_PID") != Some(std::process::id()) {
+        return Vec::new();
+    }
+    let count = var("LISTEN_FDS").unwrap_or(0) as i32;
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        // SAFETY: systemd hands these descriptors to this process and nothing else
+        // in it uses them. They arrive without FD_CLOEXEC, so set it to keep them out
+        // of the processes the server spawns.
+        .map(|fd| unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFD);
+            if flags >= 0 {
+                libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC);
+            }
+            TcpListener::from_raw_fd(fd)
+        })
+        .collect()
+}
+
+pub fn write_state(path: &Path, state: &ServerState) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(state)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn read_state(path: &Path) -> Option<ServerState> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iface(name: &str, ip: &str) -> (String, IpAddr) {
+        (name.to_string(), ip.parse().unwrap())
+    }
+
+    #[test]
+    fn selects_loopback_and_container_bridges_only() {
+        let interfaces = vec![
+            iface("lo", "127.0.0.1"),
+            iface("wlan0", "192.168.1.20"),
+            iface("podman0", "10.88.0.1"),
+            iface("docker0", "172.17.0.1"),
+            iface("tun0", "10.8.0.2"),
+        ];
+        let addrs: Vec<String> = select_addrs(&interfaces)
+            .iter()
+            .map(IpAddr::to_string)
+            .collect();
+        assert_eq!(addrs, vec!["127.0.0.1", "10.88.0.1", "172.17.0.1"]);
+    }
+
+    #[test]
+    fn configured_addresses_override_detection() {
+        let config = ServerConfig {
+            bind: Some(vec!["0.0.0.0".parse().unwrap()]),
+            ..Default::default()
+        };
+        assert_eq!(bind_addrs(&config), vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)]);
+    }
+
+    #[test]
+    fn no_listeners_are_inherited_without_socket_activation() {
+        assert!(inherited_listeners().is_empty());
+    }
+
+    #[test]
+    fn detection_always_includes_loopback() {
+        let addrs = bind_addrs(&ServerConfig::default());
+        assert_eq!(addrs[0], IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert!(!addrs.contains(&IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+    }
+}
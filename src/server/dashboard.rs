@@ -0,0 +1,200 @@
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::events::Event;
+use crate::container::{ContainerEntry, DEFAULT_SESSION, project_name};
+
+/// Events listed per project on the dashboard.
+const EVENTS_PER_PROJECT: usize = 10;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+h1 { font-size: 1.4rem; }
+h2 { font-size: 1.1rem; margin-top: 2rem; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3rem 0.8rem 0.3rem 0; vertical-align: top; }
+th { border-bottom: 1px solid #ccc; }
+code { background: #f3f3f3; padding: 0.1rem 0.3rem; }
+.muted { color: #888; }
+"#;
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn shell_quote(s: &str) -> String {
+    if s.chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-~".contains(c))
+    {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// `ai-pod attach` command for a session of a workspace.
+pub fn attach_session_command(workspace: &str, session: &str) -> String {
+    let mut cmd = format!("ai-pod attach --workdir {}", shell_quote(workspace));
+    if session != DEFAULT_SESSION && session != "-" {
+        cmd.push_str(&format!(" --session {}", session));
+    }
+    cmd
+}
+
+/// Command that reattaches to a session from a terminal.
+pub fn attach_command(workspace: &str, entry: &ContainerEntry) -> String {
+    if !workspace.starts_with('/') {
+        // Containers without a workspace label predate sessions.
+        return format!("podman exec -it {} claude", entry.name);
+    }
+    attach_session_command(workspace, &entry.session)
+}
+
+fn sessions_html(sessions: &BTreeMap<String, Vec<ContainerEntry>>) -> String {
+    if sessions.is_empty() {
+        return "<p class=\"muted\">No running sessions.</p>\n".to_string();
+    }
+    let mut html = String::from(
+        "<table>\n<tr><th>Project</th><th>Session</th><th>Container</th><th>Status</th><th>Attach</th></tr>\n",
+    );
+    for (workspace, entries) in sessions {
+        for entry in entries {
+            html.push_str(&format!(
+                "<tr><td title=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td><code>{}</code></td></tr>\n",
+                escape(workspace),
+                escape(&project_name(Path::new(workspace))),
+                escape(&entry.session),
+                escape(&entry.name),
+                escape(&entry.status),
+                escape(&attach_command(workspace, entry)),
+            ));
+        }
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn events_html(events: &[Event]) -> String {
+    if events.is_empty() {
+        return "<p class=\"muted\">No events yet.</p>\n".to_string();
+    }
+    let mut by_project: BTreeMap<&str, Vec<&Event>> = BTreeMap::new();
+    for event in events.iter().rev() {
+        let list = by_project.entry(&event.project).or_default();
+        if list.len() < EVENTS_PER_PROJECT {
+            list.push(event);
+        }
+    }
+
+    let mut html = String::new();
+    for (project, events) in by_project {
+        html.push_str(&format!("<h3>{}</h3>\n<table>\n", escape(project)));
+        for event in events {
+            html.push_str(&format!(
+                "<tr><td class=\"muted\">{}</td><td>{}</td><td><strong>{}</strong><br>{}</td></tr>\n",
+                event.time.with_timezone(&Local).format("%m-%d %H:%M:%S"),
+                escape(&event.kind),
+                escape(&event.title),
+                escape(&event.body).replace('\n', "<br>"),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html
+}
+
+/// The page served at `/`: running sessions and recent events, newest first.
+/// It reloads itself every few seconds.
+pub fn render(sessions: &BTreeMap<String, Vec<ContainerEntry>>, events: &[Event]) -> String {
+    format!(
+        "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"refresh\" content=\"5\">\n<title>ai-pod</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>ai-pod</h1>\n\
+         <h2>Running sessions</h2>\n{}<h2>Recent events</h2>\n{}</body>\n</html>\n",
+        STYLE,
+        sessions_html(sessions),
+        events_html(events)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(name: &str, session: &str) -> ContainerEntry {
+        ContainerEntry {
+            name: name.into(),
+            session: session.into(),
+            status: "Up 5 minutes".into(),
+            limits: String::new(),
+            created: String::new(),
+        }
+    }
+
+    fn event(id: u64, project: &str, title: &str) -> Event {
+        Event {
+            id,
+            time: Utc::now(),
+            kind: "Stop".into(),
+            project: project.into(),
+            container: None,
+            title: title.into(),
+            body: "line one\nline two".into(),
+        }
+    }
+
+    #[test]
+    fn attach_commands_name_workspace_and_session() {
+        assert_eq!(
+            attach_command("/home/me/app", &entry("claude-abc", "default")),
+            "ai-pod attach --workdir /home/me/app"
+        );
+        assert_eq!(
+            attach_command("/home/me/my app", &entry("claude-abc-review", "review")),
+            "ai-pod attach --workdir '/home/me/my app' --session review"
+        );
+        assert_eq!(
+            attach_command("(unknown workspace)", &entry("claude-abc", "-")),
+            "podman exec -it claude-abc claude"
+        );
+    }
+
+    #[test]
+    fn dashboard_lists_sessions_and_escapes_events() {
+        let sessions = BTreeMap::from([(
+            "/home/me/app".to_string(),
+            vec![entry("claude-abc", "default")],
+        )]);
+        let events = vec![event(1, "app", "<b>done</b>")];
+        let html = render(&sessions, &events);
+
+        assert!(html.contains("<td title=\"/home/me/app\">app</td>"));
+        assert!(html.contains("ai-pod attach --workdir /home/me/app"));
+        assert!(html.contains("&lt;b&gt;done&lt;/b&gt;"));
+        assert!(html.contains("line one<br>line two"));
+    }
+
+    #[test]
+    fn events_are_grouped_newest_first_and_capped() {
+        let events: Vec<Event> = (1..=15)
+            .map(|i| event(i, "app", &format!("t{}", i)))
+            .collect();
+        let html = events_html(&events);
+        assert!(html.find("t15").unwrap() < html.find("t14").unwrap());
+        assert!(!html.contains(">t5<"));
+        assert!(html.contains(">t6<"));
+    }
+}
@@ -0,0 +1,320 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use super::auth;
+use crate::config::AppConfig;
+
+/// Events kept in memory and served by `/api/events`.
+pub const CAPACITY: usize = 500;
+
+/// Events buffered per `/events` subscriber; slower clients skip ahead.
+const SUBSCRIBER_BACKLOG: usize = 64;
+
+/// An event shown to the user, as stored in `~/.ai-pod/events.jsonl`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    /// Hook event name (`Stop`, `Notification`, ...) or, for live subscribers only, a
+    /// container lifecycle change (`ContainerStarted`, `ContainerStopped`, `ContainerRemoved`).
+    pub kind: String,
+    pub project: String,
+    pub container: Option<String>,
+    pub title: String,
+    pub body: String,
+}
+
+struct Inner {
+    events: VecDeque<Event>,
+    next_id: u64,
+    /// Lines in the file; it is compacted to the buffer once it holds twice `CAPACITY`.
+    lines_on_disk: usize,
+}
+
+/// Ring buffer of recent events, mirrored to an append-only JSONL file so history
+/// survives server restarts. New events are also broadcast to live subscribers.
+pub struct EventLog {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+    live: broadcast::Sender<Event>,
+}
+
+fn parse_lines(raw: &str) -> Vec<Event> {
+    raw.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Events stored on disk, oldest first. A missing file is an empty history.
+pub fn read_file(path: &Path) -> Vec<Event> {
+    std::fs::read_to_string(path)
+        .map(|raw| parse_lines(&raw))
+        .unwrap_or_default()
+}
+
+impl EventLog {
+    pub fn open(path: PathBuf) -> Self {
+        let stored = read_file(&path);
+        let lines_on_disk = stored.len();
+        let next_id = stored.last().map_or(1, |e| e.id + 1);
+        let skip = stored.len().saturating_sub(CAPACITY);
+        let events = stored.into_iter().skip(skip).collect();
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                events,
+                next_id,
+                lines_on_disk,
+            }),
+            live: broadcast::channel(SUBSCRIBER_BACKLOG).0,
+        }
+    }
+
+    /// Receives every event recorded from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.live.subscribe()
+    }
+
+    /// Adds an event and persists it. Disk errors are logged, not returned: history is
+    /// best effort and must not stop notifications.
+    pub fn record(
+        &self,
+        kind: &str,
+        project: &str,
+        container: Option<&str>,
+        title: &str,
+        body: &str,
+    ) -> Event {
+        let mut inner = self.inner.lock().unwrap();
+        let event = Self::next_event(&mut inner, kind, project, container, title, body);
+        if inner.events.len() == CAPACITY {
+            inner.events.pop_front();
+        }
+        inner.events.push_back(event.clone());
+
+        if let Err(e) = self.persist(&mut inner, &event) {
+            tracing::error!("Failed to write {}: {e:#}", self.path.display());
+        }
+        // No subscribers is not an error.
+        let _ = self.live.send(event.clone());
+        event
+    }
+
+    /// Sends an event to live subscribers only, so frequent container lifecycle changes
+    /// neither fill the history file nor push notifications out of the buffer.
+    pub fn broadcast(
+        &self,
+        kind: &str,
+        project: &str,
+        container: Option<&str>,
+        title: &str,
+        body: &str,
+    ) -> Event {
+        let mut inner = self.inner.lock().unwrap();
+        let event = Self::next_event(&mut inner, kind, project, container, title, body);
+        let _ = self.live.send(event.clone());
+        event
+    }
+
+    fn next_event(
+        inner: &mut Inner,
+        kind: &str,
+        project: &str,
+        container: Option<&str>,
+        title: &str,
+        body: &str,
+    ) -> Event {
+        let event = Event {
+            id: inner.next_id,
+            time: Utc::now(),
+            kind: kind.to_string(),
+            project: project.to_string(),
+            container: container.map(str::to_string),
+            title: title.to_string(),
+            body: body.to_string(),
+        };
+        inner.next_id += 1;
+        event
+    }
+
+    fn persist(&self, inner: &mut Inner, event: &Event) -> Result<()> {
+        if inner.lines_on_disk >= 2 * CAPACITY {
+            let mut raw = String::new();
+            for e in &inner.events {
+                raw.push_str(&serde_json::to_string(e)?);
+                raw.push('\n');
+            }
+            std::fs::write(&self.path, raw)?;
+            inner.lines_on_disk = inner.events.len();
+            return Ok(());
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(event)?)?;
+        inner.lines_on_disk += 1;
+        Ok(())
+    }
+
+    /// Events with an id greater than `id`, oldest first.
+    pub fn since(&self, id: u64) -> Vec<Event> {
+        let inner = self.inner.lock().unwrap();
+        inner.events.iter().filter(|e| e.id > id).cloned().collect()
+    }
+}
+
+fn print_event(event: &Event) {
+    let time = event.time.with_timezone(&Local).format("%m-%d %H:%M:%S");
+    let body = event.body.lines().next().unwrap_or("");
+    println!(
+        "{} {:<20} {:<13} {} {}",
+        time.to_string().dimmed(),
+        event.project,
+        event.kind.cyan(),
+        event.title.bold(),
+        body
+    );
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    port: u16,
+    token: &str,
+    since: u64,
+) -> reqwest::Result<Vec<Event>> {
+    client
+        .get(format!(
+            "http://127.0.0.1:{}/api/events?since={}",
+            port, since
+        ))
+        .bearer_auth(token)
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+/// Prints the last `lines` events and, with `follow`, polls the server for new ones.
+/// Without a running server the history is read from disk.
+pub async fn tail(config: &AppConfig, port: u16, lines: usize, follow: bool) -> Result<()> {
+    let token = auth::host_token(&config.server_token)?;
+    let client = reqwest::Client::new();
+    let history = match fetch(&client, port, &token, 0).await {
+        Ok(events) => events,
+        Err(_) if !follow => read_file(&config.events_file),
+        Err(e) => {
+            return Err(e)
+                .context("Notification server is not reachable; `ai-pod` starts it on launch");
+        }
+    };
+
+    let skip = history.len().saturating_sub(lines);
+    for event in &history[skip..] {
+        print_event(event);
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut last_id = history.last().map_or(0, |e| e.id);
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        // The server may restart between polls; keep waiting instead of exiting.
+        let Ok(events) = fetch(&client, port, &token, last_id).await else {
+            continue;
+        };
+        for event in &events {
+            print_event(event);
+        }
+        if let Some(last) = events.last() {
+            last_id = last.id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(log: &EventLog, n: usize) {
+        for i in 0..n {
+            log.record(
+                "Stop",
+                "proj",
+                Some("claude-abc"),
+                "proj: task complete",
+                &i.to_string(),
+            );
+        }
+    }
+
+    #[test]
+    fn events_survive_reopening() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("events.jsonl");
+        record(&EventLog::open(path.clone()), 3);
+
+        let log = EventLog::open(path);
+        let events = log.since(0);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].body, "2");
+        assert_eq!(log.record("Stop", "proj", None, "t", "b").id, 4);
+    }
+
+    #[test]
+    fn since_returns_newer_events() {
+        let dir = TempDir::new().unwrap();
+        let log = EventLog::open(dir.path().join("events.jsonl"));
+        record(&log, 5);
+        let ids: Vec<u64> = log.since(3).iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![4, 5]);
+    }
+
+    #[test]
+    fn subscribers_receive_new_events() {
+        let dir = TempDir::new().unwrap();
+        let log = EventLog::open(dir.path().join("events.jsonl"));
+        record(&log, 1);
+        let mut rx = log.subscribe();
+        record(&log, 1);
+        assert_eq!(rx.try_recv().unwrap().id, 2);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn broadcast_events_are_not_stored() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let log = EventLog::open(path.clone());
+        let mut rx = log.subscribe();
+        log.broadcast("ContainerStarted", "proj", Some("claude-abc"), "t", "b");
+        assert_eq!(rx.try_recv().unwrap().kind, "ContainerStarted");
+        assert!(log.since(0).is_empty());
+        assert!(read_file(&path).is_empty());
+    }
+
+    #[test]
+    fn buffer_and_file_are_bounded() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let log = EventLog::open(path.clone());
+        record(&log, 2 * CAPACITY + 10);
+
+        let events = log.since(0);
+        assert_eq!(events.len(), CAPACITY);
+        assert_eq!(events.last().unwrap().id, (2 * CAPACITY + 10) as u64);
+        assert!(read_file(&path).len() < 2 * CAPACITY);
+    }
+}
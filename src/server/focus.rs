@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+
+/// Where an `ai-pod` session runs, as far as it can be told from the launching
+/// terminal's environment. Used to skip notifications for the session being looked at.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Terminal {
+    /// tmux server socket, from `$TMUX`.
+    pub tmux_socket: Option<String>,
+    /// tmux pane, from `$TMUX_PANE`.
+    pub tmux_pane: Option<String>,
+    /// X11 window of the terminal emulator, from `$WINDOWID`.
+    pub window_id: Option<String>,
+}
+
+impl Terminal {
+    pub fn detect() -> Self {
+        Self::from_env(|key| std::env::var(key).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |key| var(key).filter(|v| !v.is_empty());
+        Self {
+            // $TMUX is "<socket>,<server pid>,<session>".
+            tmux_socket: var("TMUX").and_then(|t| t.split(',').next().map(str::to_string)),
+            tmux_pane: var("TMUX_PANE"),
+            window_id: var("WINDOWID"),
+        }
+    }
+
+    /// Whether the session's terminal is in front of the user. Unknown counts as not
+    /// focused, so notifications are only dropped when focus is positively detected.
+    /// An active tmux pane alone isn't enough, since the terminal showing it may be in
+    /// the background; it only narrows down a focused terminal window.
+    pub fn is_focused(&self) -> bool {
+        let window = self
+            .window_id
+            .as_ref()
+            .and_then(|window| active_window().map(|active| active == *window));
+        if window != Some(true) {
+            return false;
+        }
+        let pane = match (&self.tmux_socket, &self.tmux_pane) {
+            (Some(socket), Some(pane)) => tmux_pane_visible(socket, pane),
+            _ => None,
+        };
+        focused(window, pane)
+    }
+}
+
+/// Focus from the window and tmux checks, each `None` when it couldn't be made. The
+/// window has to be known to be focused; tmux can only rule focus out.
+fn focused(window: Option<bool>, pane: Option<bool>) -> bool {
+    window == Some(true) && pane != Some(false)
+}
+
+fn output(cmd: &mut Command) -> Option<String> {
+    let output = cmd.stderr(Stdio::null()).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Focused X11 window id (decimal, like `$WINDOWID`).
+fn active_window() -> Option<String> {
+    output(Command::new("xdotool").arg("getactivewindow"))
+}
+
+/// Whether `pane` is the active pane of the active window of an attached tmux session.
+fn tmux_pane_visible(socket: &str, pane: &str) -> Option<bool> {
+    let flags = output(Command::new("tmux").args([
+        "-S",
+        socket,
+        "display-message",
+        "-p",
+        "-t",
+        pane,
+        "#{session_attached} #{window_active} #{pane_active}",
+    ]))?;
+    Some(pane_visible(&flags))
+}
+
+fn pane_visible(flags: &str) -> bool {
+    let mut parts = flags.split_whitespace();
+    let attached = parts
+        .next()
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or(0);
+    attached > 0 && parts.all(|flag| flag == "1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn detects_tmux_and_x11_from_env() {
+        let env = HashMap::from([
+            ("TMUX", "/tmp/tmux-1000/default,4242,0"),
+            ("TMUX_PANE", "%3"),
+            ("WINDOWID", ""),
+        ]);
+        let terminal = Terminal::from_env(|key| env.get(key).map(|v| v.to_string()));
+        assert_eq!(
+            terminal,
+            Terminal {
+                tmux_socket: Some("/tmp/tmux-1000/default".into()),
+                tmux_pane: Some("%3".into()),
+                window_id: None,
+            }
+        );
+        assert!(!Terminal::default().is_focused());
+    }
+
+    #[test]
+    fn pane_is_visible_only_when_attached_and_active() {
+        assert!(pane_visible("1 1 1"));
+        assert!(pane_visible("2 1 1"));
+        assert!(!pane_visible("0 1 1"));
+        assert!(!pane_visible("1 0 1"));
+        assert!(!pane_visible("1 1 0"));
+        assert!(!pane_visible(""));
+    }
+
+    #[test]
+    fn tmux_alone_does_not_count_as_focused() {
+        assert!(focused(Some(true), None));
+        assert!(focused(Some(true), Some(true)));
+        assert!(!focused(Some(true), Some(false)));
+        assert!(!focused(None, Some(true)));
+        assert!(!focused(Some(false), Some(true)));
+        assert!(!focused(None, None));
+    }
+}
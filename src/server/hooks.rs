@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transcript::TurnSummary;
+
+/// Longest tool detail (command, path, URL) shown in a notification body.
+const MAX_DETAIL_LEN: usize = 120;
+
+/// Longest excerpt of Claude's last message shown when a task completes.
+const MAX_MESSAGE_LEN: usize = 200;
+
+/// Claude hook events ai-pod registers in the container's settings.json.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum HookEvent {
+    Stop,
+    SubagentStop,
+    Notification,
+    PreToolUse,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 4] = [
+        Self::Stop,
+        Self::SubagentStop,
+        Self::Notification,
+        Self::PreToolUse,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stop => "Stop",
+            Self::SubagentStop => "SubagentStop",
+            Self::Notification => "Notification",
+            Self::PreToolUse => "PreToolUse",
+        }
+    }
+}
+
+/// The JSON Claude passes to hooks on stdin, as forwarded by the hook command.
+/// Only the fields ai-pod uses are modelled; all are optional because their presence
+/// depends on the event.
+#[derive(Debug, Default, Deserialize)]
+pub struct HookPayload {
+    pub hook_event_name: Option<HookEvent>,
+    pub session_id: Option<String>,
+    /// Path of the session's JSONL transcript inside the container.
+    pub transcript_path: Option<String>,
+    /// Notification text, e.g. "Claude needs your permission to use Bash".
+    pub message: Option<String>,
+    pub tool_name: Option<String>,
+    pub tool_input: Option<Value>,
+}
+
+impl HookPayload {
+    /// Parses a forwarded payload. An empty body comes from hooks installed by older
+    /// versions, which only ever fired on `Stop`.
+    pub fn parse(body: &[u8]) -> serde_json::Result<Self> {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Self {
+                hook_event_name: Some(HookEvent::Stop),
+                ..Default::default()
+            });
+        }
+        serde_json::from_slice(body)
+    }
+
+    pub fn event(&self) -> HookEvent {
+        self.hook_event_name.unwrap_or(HookEvent::Stop)
+    }
+}
+
+/// The most recent tool call of a session, used to explain permission prompts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolUse {
+    pub tool_name: String,
+    pub detail: Option<String>,
+}
+
+impl ToolUse {
+    pub fn from_payload(payload: &HookPayload) -> Option<Self> {
+        let tool_name = payload.tool_name.clone()?;
+        let input = payload.tool_input.as_ref();
+        let field = |key: &str| input.and_then(|i| i[key].as_str());
+        let detail = field("command")
+            .or_else(|| field("file_path"))
+            .or_else(|| field("url"))
+            .or_else(|| field("pattern"))
+            .map(truncate);
+        Some(Self { tool_name, detail })
+    }
+
+    fn describe(&self) -> String {
+        match &self.detail {
+            Some(detail) => format!("{}: {}", self.tool_name, detail),
+            None => self.tool_name.clone(),
+        }
+    }
+}
+
+fn truncate(s: &str) -> String {
+    let line = s.lines().next().unwrap_or("");
+    if line.chars().count() > MAX_DETAIL_LEN || line.len() < s.len() {
+        let cut: String = line.chars().take(MAX_DETAIL_LEN).collect();
+        format!("{}…", cut)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Collapses whitespace (messages are often multi-paragraph markdown) and shortens.
+fn excerpt(message: &str) -> String {
+    let flat = message.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() > MAX_MESSAGE_LEN {
+        let cut: String = flat.chars().take(MAX_MESSAGE_LEN).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        flat
+    }
+}
+
+/// Body of the "task complete" notification: Claude's last message and the number of
+/// files it changed in the turn.
+fn completion_body(summary: Option<&TurnSummary>) -> String {
+    let Some(summary) = summary else {
+        return "Claude finished and is waiting for your next prompt.".into();
+    };
+    let mut lines = Vec::new();
+    if let Some(message) = &summary.last_message {
+        lines.push(excerpt(message));
+    }
+    match summary.files_changed.len() {
+        0 => {}
+        1 => lines.push("1 file changed".into()),
+        n => lines.push(format!("{} files changed", n)),
+    }
+    if lines.is_empty() {
+        return "Claude finished and is waiting for your next prompt.".into();
+    }
+    lines.join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rendered {
+    pub title: String,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+/// What the server knows about a session beyond the payload itself.
+#[derive(Default)]
+pub struct SessionContext<'a> {
+    /// Last tool call, used to explain permission prompts.
+    pub last_tool: Option<&'a ToolUse>,
+    /// Summary of the finished turn, read from the transcript on `Stop`.
+    pub summary: Option<&'a TurnSummary>,
+}
+
+/// Turns a hook payload into a desktop notification. `PreToolUse` only updates the
+/// server's view of the session and is not shown.
+pub fn render(project: &str, payload: &HookPayload, ctx: &SessionContext) -> Option<Rendered> {
+    let rendered = match payload.event() {
+        HookEvent::PreToolUse => return None,
+        HookEvent::Stop => Rendered {
+            title: format!("{}: task complete", project),
+            body: completion_body(ctx.summary),
+            urgency: Urgency::Normal,
+        },
+        HookEvent::SubagentStop => Rendered {
+            title: format!("{}: subagent finished", project),
+            body: "A subagent completed its task.".into(),
+            urgency: Urgency::Low,
+        },
+        HookEvent::Notification => {
+            let message = payload
+                .message
+                .clone()
+                .unwrap_or_else(|| "Claude needs your attention.".into());
+            if message.contains("permission") {
+                let body = match ctx.last_tool {
+                    Some(tool) => format!("{}\n{}", message, tool.describe()),
+                    None => message,
+                };
+                Rendered {
+                    title: format!("{}: permission needed", project),
+                    body,
+                    urgency: Urgency::Critical,
+                }
+            } else {
+                Rendered {
+                    title: format!("{}: waiting for input", project),
+                    body: message,
+                    urgency: Urgency::Normal,
+                }
+            }
+        }
+    };
+    Some(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(json: &str) -> HookPayload {
+        HookPayload::parse(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn empty_body_is_a_legacy_stop() {
+        let p = HookPayload::parse(b"").unwrap();
+        assert_eq!(p.event(), HookEvent::Stop);
+        let r = render("proj", &p, &SessionContext::default()).unwrap();
+        assert_eq!(r.title, "proj: task complete");
+    }
+
+    #[test]
+    fn permission_prompt_is_critical_and_names_the_tool() {
+        let tool = ToolUse::from_payload(&payload(
+            r#"{"hook_event_name": "PreToolUse", "tool_name": "Bash", "tool_input": {"command": "rm -rf build"}}"#,
+        ))
+        .unwrap();
+        let p = payload(
+            r#"{"hook_event_name": "Notification", "message": "Claude needs your permission to use Bash"}"#,
+        );
+        let r = render(
+            "proj",
+            &p,
+            &SessionContext {
+                last_tool: Some(&tool),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.title, "proj: permission needed");
+        assert_eq!(r.urgency, Urgency::Critical);
+        assert_eq!(
+            r.body,
+            "Claude needs your permission to use Bash\nBash: rm -rf build"
+        );
+    }
+
+    #[test]
+    fn idle_notification_is_normal_urgency() {
+        let p = payload(
+            r#"{"hook_event_name": "Notification", "message": "Claude is waiting for your input"}"#,
+        );
+        let r = render("proj", &p, &SessionContext::default()).unwrap();
+        assert_eq!(r.title, "proj: waiting for input");
+        assert_eq!(r.urgency, Urgency::Normal);
+    }
+
+    #[test]
+    fn subagent_stop_is_low_urgency() {
+        let p = payload(r#"{"hook_event_name": "SubagentStop"}"#);
+        let r = render("proj", &p, &SessionContext::default()).unwrap();
+        assert_eq!(r.urgency, Urgency::Low);
+    }
+
+    #[test]
+    fn pre_tool_use_is_not_shown() {
+        let p = payload(r#"{"hook_event_name": "PreToolUse", "tool_name": "Read"}"#);
+        assert!(render("proj", &p, &SessionContext::default()).is_none());
+    }
+
+    #[test]
+    fn tool_detail_is_truncated_to_one_line() {
+        let long = "x".repeat(200);
+        let p = HookPayload {
+            tool_name: Some("Bash".into()),
+            tool_input: Some(serde_json::json!({"command": format!("echo a\n{}", long)})),
+            ..Default::default()
+        };
+        let tool = ToolUse::from_payload(&p).unwrap();
+        assert_eq!(tool.detail.as_deref(), Some("echo a…"));
+
+        let p = HookPayload {
+            tool_name: Some("Bash".into()),
+            tool_input: Some(serde_json::json!({"command": long})),
+            ..Default::default()
+        };
+        let detail = ToolUse::from_payload(&p).unwrap().detail.unwrap();
+        assert_eq!(detail.chars().count(), MAX_DETAIL_LEN + 1);
+    }
+
+    #[test]
+    fn completion_shows_last_message_and_changed_files() {
+        let summary = TurnSummary {
+            last_message: Some("Fixed the parser.\n\nAll tests pass.".into()),
+            files_changed: ["/app/a.rs".to_string(), "/app/b.rs".to_string()].into(),
+            started: None,
+        };
+        let ctx = SessionContext {
+            summary: Some(&summary),
+            ..Default::default()
+        };
+        let r = render("proj", &payload(r#"{"hook_event_name": "Stop"}"#), &ctx).unwrap();
+        assert_eq!(r.body, "Fixed the parser. All tests pass.\n2 files changed");
+
+        let empty = TurnSummary::default();
+        let ctx = SessionContext {
+            summary: Some(&empty),
+            ..Default::default()
+        };
+        let r = render("proj", &payload(r#"{"hook_event_name": "Stop"}"#), &ctx).unwrap();
+        assert!(r.body.contains("waiting for your next prompt"));
+    }
+
+    #[test]
+    fn long_messages_are_shortened() {
+        let long = "word ".repeat(100);
+        let body = excerpt(&long);
+        assert!(body.ends_with('…'));
+        assert!(body.chars().count() <= MAX_MESSAGE_LEN + 1);
+    }
+
+    #[test]
+    fn typed_payload_fields_are_parsed() {
+        let p = payload(
+            r#"{"hook_event_name": "Stop", "session_id": "abc", "transcript_path": "/home/claude/.claude/projects/-app/abc.jsonl"}"#,
+        );
+        assert_eq!(p.session_id.as_deref(), Some("abc"));
+        assert!(p.transcript_path.unwrap().ends_with("abc.jsonl"));
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let p = payload(r#"{"hook_event_name": "Stop", "stop_hook_active": false, "cwd": "/app"}"#);
+        assert_eq!(p.event(), HookEvent::Stop);
+    }
+}
@@ -1,56 +1,212 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
+use std::fs::File;
+use std::future::Future;
+use std::os::fd::AsRawFd;
 use std::path::Path;
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-fn read_pid(pid_file: &Path) -> Option<u32> {
+use super::bind::read_state;
+use super::{Health, SERVICE, VERSION, service};
+use crate::config::AppConfig;
+
+/// How long a new server gets to answer its health check.
+const START_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long `stop-server` waits for a graceful exit before killing the server.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An exclusive `flock` on a file, released when dropped (or when the process dies).
+pub struct LockFile {
+    _file: File,
+}
+
+impl LockFile {
+    /// Takes the lock, waiting for the current holder if `wait` is set. Without
+    /// `wait`, returns `None` while someone else holds it.
+    pub fn acquire(path: &Path, wait: bool) -> Result<Option<Self>> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let operation = if wait {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_EX | libc::LOCK_NB
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(err).with_context(|| format!("Failed to lock {}", path.display()));
+        }
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+pub fn read_pid(pid_file: &Path) -> Option<u32> {
     std::fs::read_to_string(pid_file)
         .ok()
         .and_then(|s| s.trim().parse().ok())
 }
 
-fn is_process_alive(pid: u32) -> bool {
+pub fn is_process_alive(pid: u32) -> bool {
     unsafe { libc::kill(pid as i32, 0) == 0 }
 }
 
-fn health_check(port: u16) -> bool {
-    let url = format!("http://127.0.0.1:{}/health", port);
-    Command::new("curl")
-        .args(["-sf", "--max-time", "2", &url])
-        .output()
-        .is_ok_and(|o| o.status.success())
+/// Executable of a running process, as the OS reports it.
+fn process_exe(pid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+        Some(exe.to_string_lossy().into_owned())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let output = Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "comm="])
+            .output()
+            .ok()?;
+        let comm = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!comm.is_empty()).then_some(comm)
+    }
+}
+
+/// Compares executable file names. Linux reports a binary replaced by an upgrade as
+/// `<path> (deleted)`, which is still the same program.
+fn same_executable(reported: &str, ours: &Path) -> bool {
+    let reported = reported.trim_end_matches(" (deleted)");
+    Path::new(reported).file_name() == ours.file_name()
+}
+
+/// Whether `pid` is a live `ai-pod` process rather than an unrelated process that was
+/// given the PID of a server that has since exited.
+pub fn is_ai_pod_process(pid: u32) -> bool {
+    if !is_process_alive(pid) {
+        return false;
+    }
+    match (process_exe(pid), std::env::current_exe()) {
+        (Some(reported), Ok(ours)) => same_executable(&reported, &ours),
+        // Can't tell; trust the PID file as before.
+        _ => true,
+    }
+}
+
+/// Runs a future to completion from synchronous code, inside or outside the runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build runtime")
+            .block_on(future),
+    }
+}
+
+/// What answers on a port.
+#[derive(Debug, PartialEq)]
+enum Probe {
+    /// Nothing is listening.
+    Free,
+    /// An `ai-pod` server. Servers from before `/health` reported a version have none.
+    AiPod { version: Option<String> },
+    /// Another program.
+    Other,
+}
+
+fn classify(success: bool, body: &str) -> Probe {
+    if let Ok(health) = serde_json::from_str::<Health>(body)
+        && health.service == SERVICE
+    {
+        return Probe::AiPod {
+            version: Some(health.version),
+        };
+    }
+    if success && body.trim() == "ok" {
+        return Probe::AiPod { version: None };
+    }
+    Probe::Other
 }
 
-pub fn is_server_running(pid_file: &Path, port: u16) -> bool {
-    if let Some(pid) = read_pid(pid_file) {
-        if is_process_alive(pid) && health_check(port) {
-            return true;
+fn probe(port: u16) -> Probe {
+    let url = format!("http://127.0.0.1:{}/health", port);
+    block_on(async {
+        let response = reqwest::Client::new()
+            .get(url)
+            .timeout(Duration::from_secs(2))
+            .send()
+            .await;
+        match response {
+            Ok(response) => {
+                let success = response.status().is_success();
+                classify(success, &response.text().await.unwrap_or_default())
+            }
+            Err(e) if e.is_connect() => Probe::Free,
+            Err(_) => Probe::Other,
         }
+    })
+}
+
+fn is_current(probe: &Probe) -> bool {
+    matches!(probe, Probe::AiPod { version: Some(v) } if v == VERSION)
+}
+
+/// A port nothing listens on right now, chosen by the OS.
+fn free_port() -> Result<u16> {
+    let listener =
+        std::net::TcpListener::bind("127.0.0.1:0").context("Failed to find a free port")?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Port of the server `ai-pod` spawned, if that is still running. Servers that did
+/// not record their port are assumed to listen on `port`.
+pub fn running_port(config: &AppConfig, port: u16) -> Option<u16> {
+    let pid = read_pid(&config.pid_file).filter(|&pid| is_ai_pod_process(pid))?;
+    let state = read_state(&config.server_state).filter(|state| state.pid == pid);
+    Some(state.map_or(port, |state| state.port))
+}
+
+/// Port to reach the server on: where the spawned server listens, or `port`.
+pub fn current_port(config: &AppConfig, port: u16) -> u16 {
+    if service::manages_port(port) {
+        return port;
     }
-    false
+    running_port(config, port).unwrap_or(port)
 }
 
-pub fn start_server(pid_file: &Path, log_file: &Path, port: u16) -> Result<()> {
+/// Starts the server as a detached child. The server writes its own PID file once it
+/// holds the server lock.
+pub fn start_server(config: &AppConfig, port: u16) -> Result<()> {
     let exe = std::env::current_exe().context("Failed to get current executable path")?;
 
-    let log = std::fs::File::create(log_file).context("Failed to create server log file")?;
-    let log_err = log.try_clone()?;
+    // The server logs to `server.log` itself; errors before it gets that far are
+    // appended to the same file.
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.log_file)
+        .context("Failed to open server log file")?;
 
     let child = Command::new(exe)
         .args(["serve-notifications", "--notify-port", &port.to_string()])
         .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::from(log))
-        .stderr(std::process::Stdio::from(log_err))
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::from(log))
         .spawn()
         .context("Failed to spawn notification server")?;
 
-    std::fs::write(pid_file, child.id().to_string())
-        .context("Failed to write PID file")?;
-
-    // Wait briefly and verify it started
-    std::thread::sleep(std::time::Duration::from_millis(500));
+    let deadline = Instant::now() + START_TIMEOUT;
+    let mut healthy = is_current(&probe(port));
+    while !healthy && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        healthy = is_current(&probe(port));
+    }
 
-    if health_check(port) {
+    if healthy {
         println!(
             "{} (PID {}, port {})",
             "Notification server started.".green(),
@@ -60,7 +216,8 @@ pub fn start_server(pid_file: &Path, log_file: &Path, port: u16) -> Result<()> {
     } else {
         println!(
             "{}",
-            "Notification server started but health check failed; it may still be initializing."
+            "Notification server started but health check failed; it may still be initializing \
+             (see `ai-pod logs server`)."
                 .yellow()
         );
     }
@@ -68,14 +225,41 @@ pub fn start_server(pid_file: &Path, log_file: &Path, port: u16) -> Result<()> {
     Ok(())
 }
 
-pub fn stop_server(pid_file: &Path) -> Result<()> {
+/// Stops a server started by [`start_server`], waiting for it to shut down.
+pub fn stop_spawned(pid_file: &Path) -> Result<()> {
     match read_pid(pid_file) {
-        Some(pid) if is_process_alive(pid) => {
+        Some(pid) if is_ai_pod_process(pid) => {
             unsafe {
                 libc::kill(pid as i32, libc::SIGTERM);
             }
+            let deadline = Instant::now() + STOP_TIMEOUT;
+            while is_process_alive(pid) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            if is_process_alive(pid) {
+                unsafe {
+                    libc::kill(pid as i32, libc::SIGKILL);
+                }
+                println!(
+                    "{} (PID {})",
+                    "Notification server did not exit in time and was killed.".yellow(),
+                    pid
+                );
+            } else {
+                println!("{} (PID {})", "Notification server stopped.".green(), pid);
+            }
+            let _ = std::fs::remove_file(pid_file);
+        }
+        Some(pid) if is_process_alive(pid) => {
             let _ = std::fs::remove_file(pid_file);
-            println!("{} (PID {})", "Notification server stopped.".green(), pid);
+            println!(
+                "{}",
+                format!(
+                    "PID {} belongs to another program; stale PID file removed.",
+                    pid
+                )
+                .yellow()
+            );
         }
         Some(_) => {
             let _ = std::fs::remove_file(pid_file);
@@ -88,15 +272,55 @@ pub fn stop_server(pid_file: &Path) -> Result<()> {
     Ok(())
 }
 
-pub fn print_status(pid_file: &Path, port: u16) {
-    match read_pid(pid_file) {
+pub fn stop_server(pid_file: &Path, port: u16) -> Result<()> {
+    if service::manages_port(port) {
+        return service::stop();
+    }
+    stop_spawned(pid_file)
+}
+
+pub fn print_status(config: &AppConfig, port: u16) {
+    let managed = service::manages_port(port);
+    if managed {
+        println!(
+            "Service: systemd user unit (socket {})",
+            service::socket_state()
+        );
+    }
+    match read_pid(&config.pid_file) {
         Some(pid) => {
-            let alive = is_process_alive(pid);
-            let healthy = if alive { health_check(port) } else { false };
+            let alive = is_ai_pod_process(pid);
+            let port = current_port(config, port);
+            let answer = if alive { probe(port) } else { Probe::Free };
+            let healthy = matches!(answer, Probe::AiPod { .. });
             println!("PID:     {}", pid);
             println!("Process: {}", if alive { "running".green() } else { "dead".red() });
             println!("Health:  {}", if healthy { "ok".green() } else { "unreachable".red() });
+            if let Probe::AiPod { version } = &answer {
+                let version = version.as_deref().unwrap_or("unknown");
+                if version == VERSION {
+                    println!("Version: {}", version);
+                } else {
+                    println!("Version: {} ({} installed)", version.yellow(), VERSION);
+                }
+            }
             println!("Port:    {}", port);
+            if let Some(state) = read_state(&config.server_state)
+                && state.pid == pid
+            {
+                let addrs: Vec<String> = state.addrs.iter().map(|a| a.to_string()).collect();
+                println!("Listen:  {}", addrs.join(", "));
+            }
+            // The token itself isn't printed, to keep it out of terminal scrollback.
+            println!("Dashboard: http://127.0.0.1:{}/", port);
+            println!(
+                "           open with: xdg-open \"http://127.0.0.1:{}/?token=$(cat '{}')\"",
+                port,
+                config.server_token.display()
+            );
+        }
+        None if managed => {
+            println!("Server starts on the first connection to its socket.");
         }
         None => {
             println!("{}", "No PID file found; server is not running.".yellow());
@@ -104,9 +328,124 @@ pub fn print_status(pid_file: &Path, port: u16) {
     }
 }
 
-pub fn ensure_server(pid_file: &Path, log_file: &Path, port: u16) -> Result<()> {
-    if is_server_running(pid_file, port) {
-        return Ok(());
+/// Makes sure a server of this version is running and returns its port, which
+/// differs from `port` when another program listens there.
+pub fn ensure_server(config: &AppConfig, port: u16) -> Result<u16> {
+    // The installed service is started by systemd on the first connection.
+    if service::manages_port(port) {
+        service::ensure_active()?;
+        if let Probe::AiPod { version } = probe(port)
+            && version.as_deref() != Some(VERSION)
+        {
+            println!(
+                "{}",
+                "Restarting the outdated notification server.".yellow()
+            );
+            service::restart()?;
+        }
+        return Ok(port);
+    }
+    // A concurrent launch waits here, then finds the server the first one started.
+    let _start = LockFile::acquire(&config.start_lock, true)?;
+    if let Some(running) = running_port(config, port) {
+        match probe(running) {
+            probe if is_current(&probe) => return Ok(running),
+            Probe::AiPod { version } => println!(
+                "{} ({} → {})",
+                "Restarting the outdated notification server.".yellow(),
+                version.as_deref().unwrap_or("unknown"),
+                VERSION
+            ),
+            _ => println!(
+                "{}",
+                "Notification server is not responding; restarting it.".yellow()
+            ),
+        }
+        stop_spawned(&config.pid_file)?;
+        // Same port, so containers that are already running still reach it.
+        start_server(config, running)?;
+        return Ok(running);
+    }
+    let port = if probe(port) == Probe::Free {
+        port
+    } else {
+        let free = free_port()?;
+        println!(
+            "{}",
+            format!(
+                "Port {} is in use by another program; using port {}.",
+                port, free
+            )
+            .yellow()
+        );
+        free
+    };
+    start_server(config, port)?;
+    Ok(port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.lock");
+        let held = LockFile::acquire(&path, false).unwrap();
+        assert!(held.is_some());
+        assert!(LockFile::acquire(&path, false).unwrap().is_none());
+        drop(held);
+        assert!(LockFile::acquire(&path, false).unwrap().is_some());
+    }
+
+    #[test]
+    fn recycled_pids_are_not_mistaken_for_the_server() {
+        let ours = Path::new("/usr/local/bin/ai-pod");
+        assert!(same_executable("/usr/local/bin/ai-pod", ours));
+        assert!(same_executable("/usr/local/bin/ai-pod (deleted)", ours));
+        assert!(!same_executable("/usr/bin/python3", ours));
+        assert!(is_ai_pod_process(std::process::id()));
+        assert!(!is_ai_pod_process(i32::MAX as u32));
+    }
+
+    #[test]
+    fn health_responses_identify_the_server_and_its_version() {
+        let health = |service: &str, version: &str| {
+            serde_json::to_string(&Health {
+                service: service.into(),
+                version: version.into(),
+                pid: 1,
+                capabilities: vec!["notify".into()],
+            })
+            .unwrap()
+        };
+        let current = classify(true, &health(SERVICE, VERSION));
+        assert!(is_current(&current));
+        let outdated = classify(true, &health(SERVICE, "0.0.1"));
+        assert_eq!(
+            outdated,
+            Probe::AiPod {
+                version: Some("0.0.1".into())
+            }
+        );
+        assert!(!is_current(&outdated));
+        assert_eq!(classify(true, "ok"), Probe::AiPod { version: None });
+        assert_eq!(classify(true, &health("grafana", VERSION)), Probe::Other);
+        assert_eq!(classify(false, "ok"), Probe::Other);
+        assert_eq!(classify(true, "<html></html>"), Probe::Other);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn probe_tells_free_ports_from_other_programs() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = axum::Router::new().route("/", axum::routing::get(|| async { "hello" }));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        assert_eq!(probe(port), Probe::Other);
+
+        let closed_port = free_port().unwrap();
+        assert_eq!(probe(closed_port), Probe::Free);
     }
-    start_server(pid_file, log_file, port)
 }
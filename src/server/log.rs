@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::user_config::ServerConfig;
+
+/// Size at which `server.log` is rotated, unless `[server] log_max_mb` says otherwise.
+const LOG_MAX_MB: u64 = 5;
+/// Rotated logs kept, unless `[server] log_files` says otherwise.
+const LOG_FILES: usize = 3;
+
+/// `<path>.<n>`; `.1` is the most recently rotated log.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+struct Current {
+    file: File,
+    len: u64,
+}
+
+/// A log file that moves to `<path>.1` once it would grow past `max_bytes`, shifting
+/// older logs up to `<path>.<keep>`. Restarts append, so a crash's log survives.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    current: Mutex<Current>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = open_append(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            keep,
+            current: Mutex::new(Current { file, len }),
+        })
+    }
+
+    fn rotate(&self) -> io::Result<File> {
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = rotated(&self.path, n);
+                if from.exists() {
+                    std::fs::rename(&from, rotated(&self.path, n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        open_append(&self.path)
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        // Each event is written in one call, so events are never split across files.
+        if current.len > 0 && current.len + buf.len() as u64 > self.max_bytes {
+            current.file = self.rotate()?;
+            current.len = 0;
+        }
+        current.file.write_all(buf)?;
+        current.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        current.file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+/// Sends the server's `tracing` output to `path` and to stdout, which is the journal
+/// when systemd runs the server. Panics are logged too, since a spawned server has no
+/// terminal to print them to.
+pub fn init(path: &Path, config: &ServerConfig) -> Result<()> {
+    let max_bytes = config.log_max_mb.unwrap_or(LOG_MAX_MB) * 1024 * 1024;
+    let keep = config.log_files.unwrap_or(LOG_FILES);
+    let file = RotatingFile::open(path, max_bytes, keep)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let subscriber = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer().with_writer(file))
+        .with(tracing_subscriber::fmt::layer().with_writer(io::stdout));
+    tracing::subscriber::set_global_default(subscriber)
+        .context("Failed to set up server logging")?;
+    std::panic::set_hook(Box::new(|info| tracing::error!("{}", info)));
+    Ok(())
+}
+
+fn last_lines(text: &str, lines: usize) -> Vec<&str> {
+    let all: Vec<&str> = text.lines().collect();
+    let skip = all.len().saturating_sub(lines);
+    all[skip..].to_vec()
+}
+
+/// `ai-pod logs server`: prints the end of the server log and, with `follow`, what
+/// the server writes after that, following it across rotations.
+pub fn tail(path: &Path, lines: usize, follow: bool) -> Result<()> {
+    let mut file = match File::open(path) {
+        Ok(file) => Some(file),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && follow => None,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            anyhow::bail!(
+                "{} does not exist yet; the server creates it when it starts",
+                path.display()
+            );
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    if let Some(file) = &mut file {
+        let mut history = Vec::new();
+        file.read_to_end(&mut history)?;
+        for line in last_lines(&String::from_utf8_lossy(&history), lines) {
+            println!("{}", line);
+        }
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    // Keep reading the open file; once it has been rotated away and is drained,
+    // switch to the new one.
+    let mut stdout = io::stdout();
+    loop {
+        if let Some(file) = &mut file {
+            io::copy(file, &mut stdout)?;
+            stdout.flush()?;
+        }
+        let replaced = match (&file, std::fs::metadata(path)) {
+            (Some(file), Ok(meta)) => file.metadata()?.ino() != meta.ino(),
+            (None, Ok(_)) => true,
+            (_, Err(_)) => false,
+        };
+        if replaced {
+            file = File::open(path).ok();
+            continue;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_line(log: &RotatingFile, line: &str) {
+        let mut writer = log.make_writer();
+        writer.write_all(format!("{}\n", line).as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn logs_rotate_by_size_and_keep_a_limited_number() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.log");
+        let log = RotatingFile::open(&path, 20, 2).unwrap();
+        for n in 1..=4 {
+            write_line(&log, &format!("line {:02} .......", n));
+        }
+        let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "line 04 .......\n");
+        assert_eq!(read(&rotated(&path, 1)), "line 03 .......\n");
+        assert_eq!(read(&rotated(&path, 2)), "line 02 .......\n");
+        assert!(!rotated(&path, 3).exists());
+    }
+
+    #[test]
+    fn reopening_appends_instead_of_truncating() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.log");
+        write_line(
+            &RotatingFile::open(&path, 1024, 3).unwrap(),
+            "before restart",
+        );
+        write_line(
+            &RotatingFile::open(&path, 1024, 3).unwrap(),
+            "after restart",
+        );
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            last_lines(&text, 10),
+            vec!["before restart", "after restart"]
+        );
+        assert_eq!(last_lines(&text, 1), vec!["after restart"]);
+    }
+}
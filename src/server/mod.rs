@@ -1,35 +1,784 @@
+pub mod auth;
+pub mod bind;
+pub mod dashboard;
+pub mod events;
+pub mod focus;
+pub mod hooks;
 pub mod lifecycle;
+pub mod log;
 pub mod notify;
+pub mod rules;
+pub mod service;
+pub mod sinks;
+pub mod transcript;
+pub mod watch;
 
-use axum::{Router, extract::Query, routing::get, routing::post};
-use serde::Deserialize;
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Query, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        Html, IntoResponse, Response,
+        sse::{self, KeepAlive, Sse},
+    },
+    routing::get,
+    routing::post,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
 
-async fn health_handler() -> &'static str {
-    "ok"
+use crate::config::AppConfig;
+use crate::user_config::UserConfig;
+use anyhow::Context;
+use bind::ServerState;
+use events::{Event, EventLog};
+use hooks::{HookEvent, HookPayload, SessionContext, ToolUse};
+use lifecycle::LockFile;
+use rules::{Candidate, Rules};
+use sinks::{Debounce, Notification, Sinks};
+use transcript::TurnSummary;
+
+/// Per-server state shared between requests.
+struct AppState {
+    /// Last `PreToolUse` per Claude session, to name the tool in permission prompts.
+    last_tool: Mutex<HashMap<String, ToolUse>>,
+    events: Arc<EventLog>,
+    /// Session tokens issued by `ai-pod` for running containers.
+    token_dir: PathBuf,
+    /// Token for host-side clients of the event API and dashboard.
+    host_token: String,
+    /// Where rendered notifications are delivered.
+    sinks: Arc<Sinks>,
+    /// Keeps bursts of `Stop` events from a container down to one notification.
+    debounce: Debounce,
+    /// Do-not-disturb rules; suppressed notifications are still recorded as events.
+    rules: Arc<Rules>,
+    /// Session history; each session's events are counted there as they arrive.
+    history_file: PathBuf,
+}
+
+/// How long open connections may keep the server up after a shutdown signal.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// Default for `[notifications] debounce_secs`.
+const DEBOUNCE_SECS: u64 = 10;
+
+impl AppState {
+    fn new(
+        events_file: PathBuf,
+        token_dir: PathBuf,
+        host_token: String,
+        sinks: Sinks,
+        debounce: Duration,
+        rules: Rules,
+        history_file: PathBuf,
+    ) -> Self {
+        Self {
+            last_tool: Mutex::new(HashMap::new()),
+            events: Arc::new(EventLog::open(events_file)),
+            token_dir,
+            host_token,
+            sinks: Arc::new(sinks),
+            debounce: Debounce::new(debounce),
+            rules: Arc::new(rules),
+            history_file,
+        }
+    }
+}
+
+/// Guards the event API and dashboard, which expose activity from every project.
+async fn require_host_token(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if auth::is_host(request.headers(), &state.host_token) {
+        return next.run(request).await;
+    }
+    // A dashboard link carries the token once; trade it for a cookie and drop it from
+    // the URL, so it doesn't stay in the address bar or the browser history.
+    let query = request.uri().query();
+    if let Some(token) = auth::query_token(query)
+        && auth::is_host_token(token, &state.host_token)
+    {
+        let rest = auth::without_token(query);
+        let mut location = request.uri().path().to_string();
+        if !rest.is_empty() {
+            location = format!("{}?{}", location, rest);
+        }
+        let headers = [
+            (header::LOCATION, location),
+            (header::SET_COOKIE, auth::host_cookie(token)),
+        ];
+        return (StatusCode::SEE_OTHER, headers).into_response();
+    }
+    (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response()
+}
+
+/// Identifies the server on `/health`, so `ai-pod` can tell it apart from another
+/// program on the port and restart it after an upgrade.
+pub const SERVICE: &str = "ai-pod";
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Features of this server version, for clients that depend on newer ones.
+const CAPABILITIES: &[&str] = &["notify", "events", "event-stream", "dashboard"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Health {
+    pub service: String,
+    pub version: String,
+    pub pid: u32,
+    pub capabilities: Vec<String>,
+}
+
+async fn health_handler() -> Json<Health> {
+    Json(Health {
+        service: SERVICE.to_string(),
+        version: VERSION.to_string(),
+        pid: std::process::id(),
+        capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+    })
+}
+
+/// Summarizes the finished turn from the transcript inside the container.
+async fn turn_summary(container: String, transcript: Option<String>) -> Option<TurnSummary> {
+    let transcript = transcript?;
+    tokio::task::spawn_blocking(move || transcript::read_tail(&container, &transcript))
+        .await
+        .ok()
+        .flatten()
+        .map(|jsonl| transcript::summarize(&jsonl))
+}
+
+/// Receives hook payloads from containers. The sender is identified by its session
+/// token, never by anything in the request it controls.
+async fn notify_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, &'static str) {
+    let owner = auth::bearer(&headers).and_then(|token| auth::find_owner(&state.token_dir, token));
+    let Some(owner) = owner else {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid token");
+    };
+    let payload = match HookPayload::parse(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::warn!("Ignoring malformed hook payload: {e}");
+            return (StatusCode::BAD_REQUEST, "malformed payload");
+        }
+    };
+    let project = owner.project.as_str();
+    let session = payload.session_id.clone().unwrap_or_default();
+    let summary = match payload.event() {
+        HookEvent::Stop => {
+            turn_summary(owner.container.clone(), payload.transcript_path.clone()).await
+        }
+        _ => None,
+    };
+
+    let rendered = {
+        let mut last_tool = state.last_tool.lock().unwrap();
+        match payload.event() {
+            HookEvent::PreToolUse => {
+                if let Some(tool) = ToolUse::from_payload(&payload) {
+                    last_tool.insert(session.clone(), tool);
+                }
+            }
+            HookEvent::Stop => {
+                last_tool.remove(&session);
+            }
+            _ => {}
+        }
+        let ctx = SessionContext {
+            last_tool: last_tool.get(&session),
+            summary: summary.as_ref(),
+        };
+        hooks::render(project, &payload, &ctx)
+    };
+
+    if let Some(r) = rendered {
+        let kind = payload.event().as_str();
+        let container = Some(owner.container.as_str());
+        state
+            .events
+            .record(kind, project, container, &r.title, &r.body);
+        if let Some(id) = &owner.history_id
+            && let Err(e) = crate::history::record_event(&state.history_file, id, kind)
+        {
+            tracing::warn!("Could not record event in session history: {e:#}");
+        }
+        // Still recorded above: only the notification is skipped.
+        let repeatable = matches!(payload.event(), HookEvent::Stop | HookEvent::SubagentStop);
+        let key = format!("{}/{}", owner.container, kind);
+        if repeatable && !state.debounce.allow(&key, Instant::now()) {
+            return (StatusCode::OK, "ok");
+        }
+        let candidate = Candidate {
+            project: project.to_string(),
+            event: payload.event(),
+            urgency: r.urgency,
+            task_started: summary.and_then(|s| s.started).or(owner.started),
+            terminal: owner.terminal.clone(),
+        };
+        let notification = Notification {
+            kind: kind.to_string(),
+            project: project.to_string(),
+            container: Some(owner.container.clone()),
+            workspace: Some(owner.workspace.clone()),
+            session: Some(owner.session.clone()),
+            title: r.title,
+            body: r.body,
+            urgency: r.urgency,
+        };
+        // Slow sinks must not hold up the hook, which waits for the response.
+        let sinks = state.sinks.clone();
+        let rules = state.rules.clone();
+        tokio::spawn(async move {
+            let check = move || rules.suppress(&candidate, chrono::Local::now());
+            if let Ok(Some(reason)) = tokio::task::spawn_blocking(check).await {
+                tracing::info!("Not notifying \"{}\": {}", notification.title, reason);
+                return;
+            }
+            sinks.dispatch(&notification).await
+        });
+    }
+    (StatusCode::OK, "ok")
 }
 
 #[derive(Deserialize)]
-struct NotifyParams {
-    project: Option<String>,
+struct EventsParams {
+    #[serde(default)]
+    since: u64,
+}
+
+async fn events_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<EventsParams>,
+) -> Json<Vec<Event>> {
+    Json(state.events.since(params.since))
 }
 
-async fn notify_handler(Query(params): Query<NotifyParams>) -> &'static str {
-    let title = params.project.as_deref().unwrap_or("Claude Code");
-    notify::send_notification(title, "ai-pod: task complete");
-    "ok"
+async fn dashboard_handler(State(state): State<Arc<AppState>>) -> Html<String> {
+    let sessions = tokio::task::spawn_blocking(|| crate::container::query_containers(false))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+    Html(dashboard::render(&sessions, &state.events.since(0)))
 }
 
-pub async fn run_server(port: u16) -> anyhow::Result<()> {
-    let app = Router::new()
+#[derive(Deserialize)]
+struct StreamParams {
+    /// Comma-separated event kinds to receive, e.g. `Stop,Notification`; all if unset.
+    #[serde(rename = "type")]
+    types: Option<String>,
+}
+
+/// Server-sent events: every event recorded from now on, as JSON.
+async fn stream_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<StreamParams>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let types: Option<Vec<String>> = params.types.map(|t| {
+        t.split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect()
+    });
+    // A lagging subscriber gets an error for the skipped events; it just continues.
+    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(move |event| {
+        let event = event.ok()?;
+        if types.as_ref().is_some_and(|t| !t.contains(&event.kind)) {
+            return None;
+        }
+        let data = sse::Event::default()
+            .id(event.id.to_string())
+            .json_data(&event)
+            .ok()?;
+        Some(Ok(data))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn router(state: Arc<AppState>) -> Router {
+    let host_only = Router::new()
+        .route("/", get(dashboard_handler))
+        .route("/events", get(stream_handler))
+        .route("/api/events", get(events_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_host_token,
+        ));
+    Router::new()
         .route("/health", get(health_handler))
-        .route("/notify", post(notify_handler));
+        .route("/notify", post(notify_handler))
+        .merge(host_only)
+        .with_state(state)
+}
+
+/// Resolves on SIGTERM (`stop-server`, systemd) or SIGINT.
+async fn shutdown_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+pub async fn run_server(port: u16, config: &AppConfig) -> anyhow::Result<()> {
+    let _lock = LockFile::acquire(&config.server_lock, false)?.with_context(|| {
+        let pid = lifecycle::read_pid(&config.pid_file);
+        let pid = pid.map_or("unknown".to_string(), |pid| pid.to_string());
+        format!(
+            "Another notification server is already running (PID {})",
+            pid
+        )
+    })?;
+    let host_token = auth::host_token(&config.server_token)?;
+    let user_config = UserConfig::load(&config.config_file)?;
+    log::init(&config.log_file, &user_config.server)?;
+    let notifications = &user_config.notifications;
+    let sinks = Sinks::from_config(notifications)
+        .with_context(|| format!("Invalid {}", config.config_file.display()))?;
+    let debounce = notifications.debounce_secs.unwrap_or(DEBOUNCE_SECS);
+    let rules = Rules::from_config(notifications, config.mutes_file.clone())
+        .with_context(|| format!("Invalid {}", config.config_file.display()))?;
+    let state = Arc::new(AppState::new(
+        config.events_file.clone(),
+        config.token_dir.clone(),
+        host_token,
+        sinks,
+        Duration::from_secs(debounce),
+        rules,
+        config.history_file.clone(),
+    ));
+
+    // Under systemd socket activation the sockets are already bound. Otherwise the
+    // first address (loopback unless configured otherwise) is required; bridges that
+    // disappeared since detection are skipped.
+    let mut listeners = Vec::new();
+    for listener in bind::inherited_listeners() {
+        listener.set_nonblocking(true)?;
+        listeners.push(tokio::net::TcpListener::from_std(listener)?);
+    }
+    let addrs = if listeners.is_empty() {
+        bind::bind_addrs(&user_config.server)
+    } else {
+        Vec::new()
+    };
+    for ip in addrs {
+        let addr = SocketAddr::new(ip, port);
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) if listeners.is_empty() => {
+                return Err(e).with_context(|| format!("Failed to listen on {}", addr));
+            }
+            Err(e) => tracing::warn!("Not listening on {}: {}", addr, e),
+        }
+    }
+    let addrs: Vec<SocketAddr> = listeners
+        .iter()
+        .filter_map(|l| l.local_addr().ok())
+        .collect();
+    let list: Vec<String> = addrs.iter().map(SocketAddr::to_string).collect();
+    tracing::info!("Notification server listening on {}", list.join(", "));
+    bind::write_state(
+        &config.server_state,
+        &ServerState {
+            pid: std::process::id(),
+            port,
+            addrs,
+        },
+    )?;
+
+    let pid = std::process::id();
+    std::fs::write(&config.pid_file, pid.to_string()).context("Failed to write PID file")?;
+
+    let events = state.events.clone();
+    tokio::spawn(watch::watch_containers(events, config.token_dir.clone()));
+    let app = router(state);
+
+    let (stop, stopping) = tokio::sync::watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = stop.send(true);
+    });
+    let mut servers = tokio::task::JoinSet::new();
+    for listener in listeners {
+        let mut stopping = stopping.clone();
+        let shutdown = async move {
+            let _ = stopping.wait_for(|stop| *stop).await;
+        };
+        servers.spawn(
+            axum::serve(listener, app.clone())
+                .with_graceful_shutdown(shutdown)
+                .into_future(),
+        );
+    }
+
+    // Open event streams never finish on their own; give other requests a moment.
+    let mut stopping = stopping.clone();
+    let grace = async move {
+        let _ = stopping.wait_for(|stop| *stop).await;
+        tokio::time::sleep(SHUTDOWN_GRACE).await;
+    };
+    let served = async {
+        while let Some(result) = servers.join_next().await {
+            result??;
+        }
+        anyhow::Ok(())
+    };
+    let result = tokio::select! {
+        result = served => result,
+        _ = grace => Ok(()),
+    };
+
+    if lifecycle::read_pid(&config.pid_file) == Some(pid) {
+        let _ = std::fs::remove_file(&config.pid_file);
+    }
+    if bind::read_state(&config.server_state).is_some_and(|s| s.pid == pid) {
+        let _ = std::fs::remove_file(&config.server_state);
+    }
+    tracing::info!("Notification server stopped");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const HOST_TOKEN: &str = "host-secret";
+
+    async fn start(dir: &TempDir) -> String {
+        start_with_sinks(dir, Sinks::default()).await
+    }
+
+    async fn start_with_sinks(dir: &TempDir, sinks: Sinks) -> String {
+        let state = Arc::new(AppState::new(
+            dir.path().join("events.jsonl"),
+            dir.path().join("tokens"),
+            HOST_TOKEN.into(),
+            sinks,
+            Duration::from_secs(DEBOUNCE_SECS),
+            mute_rules(dir),
+            dir.path().join("history.jsonl"),
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+        format!("http://{}", addr)
+    }
+
+    fn mute_rules(dir: &TempDir) -> Rules {
+        let config = crate::user_config::NotificationsConfig::default();
+        Rules::from_config(&config, dir.path().join("mutes.json")).unwrap()
+    }
+
+    fn issue_token(dir: &TempDir, project: &str) -> String {
+        let owner = auth::TokenOwner {
+            project: project.into(),
+            container: format!("claude-{}", project),
+            workspace: format!("/home/me/{}", project),
+            session: "default".into(),
+            started: Some(chrono::Utc::now()),
+            terminal: focus::Terminal::default(),
+            history_id: Some(format!("session-{}", project)),
+        };
+        auth::issue(&dir.path().join("tokens"), owner).unwrap()
+    }
+
+    /// Reads SSE chunks until an event with `kind` arrives; returns everything read.
+    async fn read_until(response: &mut reqwest::Response, kind: &str) -> String {
+        let mut received = String::new();
+        let needle = format!("\"kind\":\"{}\"", kind);
+        while !received.contains(&needle) {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+                .await
+                .expect("timed out waiting for event")
+                .unwrap()
+                .expect("stream ended");
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn event_stream_delivers_filtered_notify_events() {
+        let dir = TempDir::new().unwrap();
+        let base = start(&dir).await;
+        let client = reqwest::Client::new();
+
+        let token = issue_token(&dir, "demo");
+
+        let mut stream = client
+            .get(format!("{}/events?type=Stop", base))
+            .bearer_auth(HOST_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stream.headers()["content-type"], "text/event-stream");
+
+        for body in [
+            r#"{"hook_event_name": "SubagentStop"}"#,
+            r#"{"hook_event_name": "Stop", "session_id": "s1"}"#,
+        ] {
+            let status = client
+                .post(format!("{}/notify", base))
+                .bearer_auth(&token)
+                .body(body)
+                .send()
+                .await
+                .unwrap()
+                .status();
+            assert!(status.is_success());
+        }
+
+        let received = read_until(&mut stream, "Stop").await;
+        assert!(received.contains("data: {"));
+        assert!(received.contains("\"title\":\"demo: task complete\""));
+        assert!(!received.contains("SubagentStop"));
+
+        let history: Vec<Event> = client
+            .get(format!("{}/api/events?since=0", base))
+            .bearer_auth(HOST_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn requests_without_valid_tokens_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let base = start(&dir).await;
+        let client = reqwest::Client::new();
+        let token = issue_token(&dir, "other");
+
+        let status = |response: reqwest::Response| response.status();
+        let anonymous = client.post(format!("{}/notify?project=demo", base)).send();
+        assert_eq!(status(anonymous.await.unwrap()), StatusCode::UNAUTHORIZED);
+
+        let forged = client
+            .post(format!("{}/notify", base))
+            .bearer_auth("forged")
+            .send();
+        assert_eq!(status(forged.await.unwrap()), StatusCode::UNAUTHORIZED);
+
+        // A session token does not grant access to other projects' events.
+        let events = client
+            .get(format!("{}/api/events", base))
+            .bearer_auth(&token)
+            .send();
+        assert_eq!(status(events.await.unwrap()), StatusCode::UNAUTHORIZED);
+
+        let health = client.get(format!("{}/health", base)).send();
+        let health: Health = health.await.unwrap().json().await.unwrap();
+        assert_eq!(health.service, SERVICE);
+        assert_eq!(health.version, VERSION);
+        assert_eq!(health.pid, std::process::id());
+        assert!(health.capabilities.contains(&"notify".to_string()));
+    }
+
+    #[tokio::test]
+    async fn dashboard_links_trade_the_token_for_a_cookie() {
+        let dir = TempDir::new().unwrap();
+        let base = start(&dir).await;
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+
+        let link = client
+            .get(format!("{}/api/events?token={}&since=0", base, HOST_TOKEN))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(link.status(), StatusCode::SEE_OTHER);
+        assert_eq!(link.headers()["location"], "/api/events?since=0");
+        let cookie = link.headers()["set-cookie"].to_str().unwrap();
+        assert!(cookie.starts_with(&format!("ai_pod_token={};", HOST_TOKEN)));
+        assert!(cookie.contains("HttpOnly"));
+
+        let page = client
+            .get(format!("{}/api/events?since=0", base))
+            .header("cookie", format!("ai_pod_token={}", HOST_TOKEN))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(page.status(), StatusCode::OK);
+
+        let wrong = client.get(format!("{}/?token=guess", base)).send();
+        assert_eq!(wrong.await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn events_are_attributed_to_the_token_owner() {
+        let dir = TempDir::new().unwrap();
+        let base = start(&dir).await;
+        let client = reqwest::Client::new();
+        let token = issue_token(&dir, "real");
+
+        client
+            .post(format!("{}/notify?project=spoofed", base))
+            .bearer_auth(&token)
+            .body(r#"{"hook_event_name": "SubagentStop"}"#)
+            .send()
+            .await
+            .unwrap();
+
+        let history: Vec<Event> = client
+            .get(format!("{}/api/events", base))
+            .bearer_auth(HOST_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(history[0].project, "real");
+        assert_eq!(history[0].container.as_deref(), Some("claude-real"));
+        let sessions = std::fs::read_to_string(dir.path().join("history.jsonl")).unwrap();
+        assert_eq!(
+            sessions,
+            "{\"id\":\"session-real\",\"event\":\"SubagentStop\"}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_payloads_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let base = start(&dir).await;
+        let token = issue_token(&dir, "demo");
+        let status = reqwest::Client::new()
+            .post(format!("{}/notify", base))
+            .bearer_auth(&token)
+            .body("{not json")
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    /// Sink that remembers the kinds of the notifications it was sent.
+    struct Recording(Arc<Mutex<Vec<String>>>);
+
+    impl sinks::NotificationSink for Recording {
+        fn name(&self) -> String {
+            "recording".into()
+        }
+
+        fn send<'a>(&'a self, n: &'a Notification) -> sinks::SendFuture<'a> {
+            self.0.lock().unwrap().push(n.kind.clone());
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_stops_notify_once_but_are_all_recorded() {
+        let dir = TempDir::new().unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sinks = Sinks::default().with(Box::new(Recording(sent.clone())), None);
+        let base = start_with_sinks(&dir, sinks).await;
+        let client = reqwest::Client::new();
+        let token = issue_token(&dir, "demo");
+
+        for body in [
+            r#"{"hook_event_name": "Stop"}"#,
+            r#"{"hook_event_name": "Stop"}"#,
+            r#"{"hook_event_name": "Notification", "message": "Claude needs your permission"}"#,
+            r#"{"hook_event_name": "Notification", "message": "Claude needs your permission"}"#,
+        ] {
+            client
+                .post(format!("{}/notify", base))
+                .bearer_auth(&token)
+                .body(body)
+                .send()
+                .await
+                .unwrap();
+        }
+
+        // Dispatch runs in the background.
+        for _ in 0..50 {
+            if sent.lock().unwrap().len() >= 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // Each notification is dispatched from its own task, in no particular order.
+        let mut sent = sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent, vec!["Notification", "Notification", "Stop"]);
+        let history: Vec<Event> = client
+            .get(format!("{}/api/events", base))
+            .bearer_auth(HOST_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 4);
+    }
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    println!("Notification server listening on {}", addr);
+    #[tokio::test]
+    async fn muted_projects_are_recorded_but_not_notified() {
+        let dir = TempDir::new().unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sinks = Sinks::default().with(Box::new(Recording(sent.clone())), None);
+        let base = start_with_sinks(&dir, sinks).await;
+        let client = reqwest::Client::new();
+        rules::mute(&dir.path().join("mutes.json"), "quiet", None).unwrap();
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+        for project in ["quiet", "loud"] {
+            let token = issue_token(&dir, project);
+            client
+                .post(format!("{}/notify", base))
+                .bearer_auth(&token)
+                .body(r#"{"hook_event_name": "Notification", "message": "Claude is waiting for your input"}"#)
+                .send()
+                .await
+                .unwrap();
+        }
 
-    Ok(())
+        for _ in 0..50 {
+            if !sent.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sent.lock().unwrap().len(), 1);
+        let history: Vec<Event> = client
+            .get(format!("{}/api/events", base))
+            .bearer_auth(HOST_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let projects: Vec<&str> = history.iter().map(|e| e.project.as_str()).collect();
+        assert_eq!(projects, vec!["quiet", "loud"]);
+    }
 }
@@ -1,10 +1,96 @@
-pub fn send_notification(title: &str, message: &str) {
-    if let Err(e) = notify_rust::Notification::new()
-        .summary(title)
-        .body(message)
-        .show()
-    {
-        eprintln!("[notify] Failed to send notification: {e}");
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::hooks::Urgency;
+
+/// Notifications with actions whose answer is still awaited, each by its own thread.
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+/// Past this many, notifications are sent without actions instead of starting more threads.
+const MAX_WAITING: usize = 8;
+
+/// Notifications with actions expire after this long, ending the wait for an answer on
+/// daemons that honour it.
+#[cfg(all(unix, not(target_os = "macos")))]
+const ACTION_TIMEOUT_MS: u32 = 10 * 60 * 1000;
+
+/// Takes a waiting slot, if one is free; it is released when dropped.
+struct WaitSlot;
+
+impl WaitSlot {
+    fn take() -> Option<Self> {
+        WAITING
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < MAX_WAITING).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for WaitSlot {
+    fn drop(&mut self) {
+        WAITING.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn build(title: &str, message: &str, urgency: Urgency) -> notify_rust::Notification {
+    let mut notification = notify_rust::Notification::new();
+    notification.summary(title).body(message);
+    #[cfg(all(unix, not(target_os = "macos")))]
+    notification.urgency(match urgency {
+        Urgency::Low => notify_rust::Urgency::Low,
+        Urgency::Normal => notify_rust::Urgency::Normal,
+        Urgency::Critical => notify_rust::Urgency::Critical,
+    });
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    let _ = urgency;
+    notification
+}
+
+/// Urgency is only honoured by freedesktop notification daemons; on macOS it is ignored.
+pub fn send_notification(title: &str, message: &str, urgency: Urgency) {
+    if let Err(e) = build(title, message, urgency).show() {
+        tracing::warn!("Failed to send notification: {e}");
+    }
+}
+
+/// Like [`send_notification`], with "Open" and "Dismiss" actions; `on_open` runs when
+/// the user picks "Open" or clicks the notification. Actions need a freedesktop
+/// notification daemon; elsewhere, and while too many notifications await an answer,
+/// this is a plain notification.
+pub fn send_notification_with_open(
+    title: &str,
+    message: &str,
+    urgency: Urgency,
+    on_open: impl FnOnce() + Send + 'static,
+) {
+    let Some(slot) = WaitSlot::take() else {
+        return send_notification(title, message, urgency);
+    };
+    let mut notification = build(title, message, urgency);
+    // The "default" action is the click on the notification; daemons that show it as a
+    // button label it "Open".
+    #[cfg(all(unix, not(target_os = "macos")))]
+    notification
+        .action("default", "Open")
+        .action("dismiss", "Dismiss")
+        .timeout(notify_rust::Timeout::Milliseconds(ACTION_TIMEOUT_MS));
+    match notification.show() {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        Ok(handle) => {
+            // Waiting blocks until the notification is closed or expires.
+            std::thread::spawn(move || {
+                let _slot = slot;
+                handle.wait_for_action(|action| {
+                    if action == "default" {
+                        on_open();
+                    }
+                })
+            });
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        Ok(_) => drop((on_open, slot)),
+        Err(e) => tracing::warn!("Failed to send notification: {e}"),
     }
 }
 
@@ -14,16 +100,35 @@ mod tests {
 
     #[test]
     fn send_notification_does_not_panic_with_normal_strings() {
-        send_notification("Claude Code", "Task completed.");
+        send_notification("Claude Code", "Task completed.", Urgency::Normal);
     }
 
     #[test]
     fn send_notification_does_not_panic_with_quotes() {
-        send_notification(r#"Title "quoted""#, r#"Message "quoted""#);
+        send_notification(r#"Title "quoted""#, r#"Message "quoted""#, Urgency::Normal);
     }
 
     #[test]
     fn send_notification_does_not_panic_with_empty_strings() {
-        send_notification("", "");
+        send_notification("", "", Urgency::Low);
+    }
+
+    #[test]
+    fn send_notification_does_not_panic_with_critical_urgency() {
+        send_notification("proj: permission needed", "Bash: ls", Urgency::Critical);
+    }
+
+    #[test]
+    fn send_notification_with_open_does_not_panic() {
+        send_notification_with_open("proj: task complete", "Done.", Urgency::Normal, || {});
+    }
+
+    #[test]
+    fn waiting_slots_are_bounded_and_released() {
+        let slots: Vec<WaitSlot> = std::iter::from_fn(WaitSlot::take).take(100).collect();
+        assert!(slots.len() <= MAX_WAITING);
+        assert!(WaitSlot::take().is_none());
+        drop(slots);
+        assert!(WaitSlot::take().is_some());
     }
 }
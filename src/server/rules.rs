@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, Utc};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::focus::Terminal;
+use super::hooks::{HookEvent, Urgency};
+use crate::config::AppConfig;
+use crate::user_config::{NotificationsConfig, UserConfig};
+
+/// Mutes set with `ai-pod notify mute`: project → end of the mute, `None` until unmuted.
+pub type Mutes = BTreeMap<String, Option<DateTime<Utc>>>;
+
+/// A daily local time range, e.g. `22:00-07:00`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn parse(range: &str) -> Result<Self> {
+        let time = |s: &str| {
+            NaiveTime::parse_from_str(s.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time '{}' (expected HH:MM)", s.trim()))
+        };
+        let (start, end) = range
+            .split_once('-')
+            .with_context(|| format!("Invalid quiet hours '{}' (expected HH:MM-HH:MM)", range))?;
+        Ok(Self {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+
+    /// Ranges that end before they start span midnight.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// A notification about to be sent, with what the rules look at.
+pub struct Candidate {
+    pub project: String,
+    pub event: HookEvent,
+    pub urgency: Urgency,
+    /// When the finished task started: its prompt if known, else the session start.
+    pub task_started: Option<DateTime<Utc>>,
+    pub terminal: Terminal,
+}
+
+/// Do-not-disturb rules from `[notifications]` plus mutes set from the command line.
+pub struct Rules {
+    quiet_hours: Option<QuietHours>,
+    muted: Vec<String>,
+    min_task: TimeDelta,
+    suppress_when_focused: bool,
+    critical_bypasses_quiet: bool,
+    mutes_file: PathBuf,
+}
+
+impl Rules {
+    pub fn from_config(config: &NotificationsConfig, mutes_file: PathBuf) -> Result<Self> {
+        let quiet_hours = config
+            .quiet_hours
+            .as_deref()
+            .map(QuietHours::parse)
+            .transpose()?;
+        let min_task = TimeDelta::seconds(config.min_task_secs.unwrap_or(0) as i64);
+        Ok(Self {
+            quiet_hours,
+            muted: config.muted.clone(),
+            min_task,
+            suppress_when_focused: config.suppress_when_focused.unwrap_or(true),
+            critical_bypasses_quiet: config.critical_bypasses_quiet.unwrap_or(true),
+            mutes_file,
+        })
+    }
+
+    /// Why the notification should not be sent, if it shouldn't. Permission prompts
+    /// block the session, so by default they get through quiet hours and mutes. The
+    /// focus check runs external commands, so it comes last.
+    pub fn suppress(&self, candidate: &Candidate, now: DateTime<Local>) -> Option<String> {
+        let project = &candidate.project;
+        let quiet = !(self.critical_bypasses_quiet && candidate.urgency == Urgency::Critical);
+        if quiet && self.muted.contains(project) {
+            return Some(format!("{} is muted in config.toml", project));
+        }
+        if quiet
+            && let Some(until) = read_mutes(&self.mutes_file).get(project)
+            && until.is_none_or(|until| until > now.to_utc())
+        {
+            return Some(format!("{} is muted", project));
+        }
+        if quiet && self.quiet_hours.is_some_and(|q| q.contains(now.time())) {
+            return Some("quiet hours".into());
+        }
+        if candidate.event == HookEvent::Stop
+            && let Some(started) = candidate.task_started
+            && now.to_utc() - started < self.min_task
+        {
+            return Some(format!(
+                "task took less than {}s",
+                self.min_task.num_seconds()
+            ));
+        }
+        if self.suppress_when_focused && candidate.terminal.is_focused() {
+            return Some("terminal is focused".into());
+        }
+        None
+    }
+}
+
+/// Parses durations like `90s`, `30m`, `1h` or `2d`.
+pub fn parse_duration(s: &str) -> Result<TimeDelta> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let n: i64 = number
+        .parse()
+        .with_context(|| format!("Invalid duration '{}' (e.g. 30m, 1h, 2d)", s))?;
+    let duration = match unit {
+        "s" => TimeDelta::try_seconds(n),
+        "m" => TimeDelta::try_minutes(n),
+        "h" | "" => TimeDelta::try_hours(n),
+        "d" => TimeDelta::try_days(n),
+        _ => anyhow::bail!("Invalid duration unit '{}' (use s, m, h or d)", unit),
+    };
+    duration.with_context(|| format!("Duration '{}' is too long", s))
+}
+
+/// `now + duration` for durations from the command line, which may be out of range.
+pub fn offset(now: DateTime<Utc>, duration: TimeDelta) -> Result<DateTime<Utc>> {
+    now.checked_add_signed(duration)
+        .context("Duration is too long")
+}
+
+pub fn read_mutes(path: &Path) -> Mutes {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Saves `mutes` without the ones that have already ended.
+fn write_mutes(path: &Path, mut mutes: Mutes, now: DateTime<Utc>) -> Result<()> {
+    mutes.retain(|_, until| until.is_none_or(|until| until > now));
+    std::fs::write(path, serde_json::to_string_pretty(&mutes)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn mute(path: &Path, project: &str, until: Option<DateTime<Utc>>) -> Result<()> {
+    let mut mutes = read_mutes(path);
+    mutes.insert(project.to_string(), until);
+    write_mutes(path, mutes, Utc::now())
+}
+
+/// Returns whether the project was muted.
+pub fn unmute(path: &Path, project: &str) -> Result<bool> {
+    let mut mutes = read_mutes(path);
+    let was_muted = mutes.remove(project).is_some();
+    write_mutes(path, mutes, Utc::now())?;
+    Ok(was_muted)
+}
+
+/// `ai-pod notify mute`.
+pub fn mute_command(config: &AppConfig, project: &str, duration: Option<&str>) -> Result<()> {
+    let until = duration
+        .map(|d| offset(Utc::now(), parse_duration(d)?))
+        .transpose()?;
+    mute(&config.mutes_file, project, until)?;
+    match until {
+        Some(until) => println!(
+            "{} {} until {}",
+            "Muted".green(),
+            project,
+            until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        None => println!(
+            "{} {} until `ai-pod notify unmute {}`",
+            "Muted".green(),
+            project,
+            project
+        ),
+    }
+    Ok(())
+}
+
+/// `ai-pod notify unmute`.
+pub fn unmute_command(config: &AppConfig, project: &str) -> Result<()> {
+    if unmute(&config.mutes_file, project)? {
+        println!("{} {}", "Unmuted".green(), project);
+    } else {
+        println!("{} is not muted.", project);
+    }
+    Ok(())
+}
+
+/// `ai-pod notify status`: the rules in effect.
+pub fn print_status(config: &AppConfig) -> Result<()> {
+    let user_config = UserConfig::load(&config.config_file)?;
+    let notifications = &user_config.notifications;
+    let now = Utc::now();
+
+    let mutes: Vec<(String, Option<DateTime<Utc>>)> = read_mutes(&config.mutes_file)
+        .into_iter()
+        .filter(|(_, until)| until.is_none_or(|until| until > now))
+        .collect();
+    if mutes.is_empty() && notifications.muted.is_empty() {
+        println!("Muted:        {}", "none".dimmed());
+    }
+    for project in &notifications.muted {
+        println!("Muted:        {} (config.toml)", project);
+    }
+    for (project, until) in mutes {
+        match until {
+            Some(until) => println!(
+                "Muted:        {} until {}",
+                project,
+                until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => println!("Muted:        {}", project),
+        }
+    }
+    println!(
+        "Quiet hours:  {}",
+        notifications.quiet_hours.as_deref().unwrap_or("off")
+    );
+    println!(
+        "Min. task:    {}s",
+        notifications.min_task_secs.unwrap_or(0)
+    );
+    let critical = notifications.critical_bypasses_quiet.unwrap_or(true);
+    println!(
+        "Critical:     {}",
+        if critical {
+            "ignore quiet hours and mutes"
+        } else {
+            "quiet hours and mutes apply"
+        }
+    );
+    let focused = notifications.suppress_when_focused.unwrap_or(true);
+    println!(
+        "When focused: {}",
+        if focused { "suppressed" } else { "notify" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 6, 1, hour, minute, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    fn candidate(project: &str, event: HookEvent) -> Candidate {
+        Candidate {
+            project: project.into(),
+            event,
+            urgency: Urgency::Normal,
+            task_started: None,
+            terminal: Terminal::default(),
+        }
+    }
+
+    fn rules(dir: &TempDir, toml: &str) -> Rules {
+        let config = UserConfig::parse(toml).unwrap();
+        Rules::from_config(&config.notifications, dir.path().join("mutes.json")).unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_may_span_midnight() {
+        let night = QuietHours::parse("22:00-07:00").unwrap();
+        assert!(night.contains(at(23, 30).time()));
+        assert!(night.contains(at(6, 59).time()));
+        assert!(!night.contains(at(7, 0).time()));
+        assert!(!night.contains(at(12, 0).time()));
+
+        let lunch = QuietHours::parse("12:00 - 13:00").unwrap();
+        assert!(lunch.contains(at(12, 30).time()));
+        assert!(!lunch.contains(at(23, 0).time()));
+
+        assert!(QuietHours::parse("22:00").is_err());
+        assert!(QuietHours::parse("25:00-07:00").is_err());
+    }
+
+    #[test]
+    fn rules_suppress_muted_projects_and_quiet_hours() {
+        let dir = TempDir::new().unwrap();
+        let rules = rules(
+            &dir,
+            "[notifications]\nquiet_hours = \"22:00-07:00\"\nmuted = [\"scratch\"]",
+        );
+        let stop = candidate("app", HookEvent::Stop);
+        assert_eq!(rules.suppress(&stop, at(12, 0)), None);
+        assert_eq!(
+            rules.suppress(&stop, at(23, 0)).as_deref(),
+            Some("quiet hours")
+        );
+        let muted = candidate("scratch", HookEvent::Notification);
+        assert!(rules.suppress(&muted, at(12, 0)).is_some());
+    }
+
+    #[test]
+    fn permission_prompts_get_through_quiet_hours_and_mutes() {
+        let dir = TempDir::new().unwrap();
+        let toml = "[notifications]\nquiet_hours = \"22:00-07:00\"\nmuted = [\"scratch\"]";
+        let lenient = rules(&dir, toml);
+        mute(&dir.path().join("mutes.json"), "app", None).unwrap();
+        for project in ["app", "scratch"] {
+            let mut prompt = candidate(project, HookEvent::Notification);
+            prompt.urgency = Urgency::Critical;
+            assert_eq!(lenient.suppress(&prompt, at(23, 0)), None);
+        }
+
+        let strict = rules(&dir, &format!("{}\ncritical_bypasses_quiet = false", toml));
+        let mut prompt = candidate("other", HookEvent::Notification);
+        prompt.urgency = Urgency::Critical;
+        assert_eq!(
+            strict.suppress(&prompt, at(23, 0)).as_deref(),
+            Some("quiet hours")
+        );
+    }
+
+    #[test]
+    fn short_tasks_do_not_notify_on_stop() {
+        let dir = TempDir::new().unwrap();
+        let rules = rules(&dir, "[notifications]\nmin_task_secs = 60");
+        let now = at(12, 0);
+        let mut stop = candidate("app", HookEvent::Stop);
+        stop.task_started = Some(now.to_utc() - TimeDelta::seconds(20));
+        assert!(rules.suppress(&stop, now).is_some());
+
+        stop.task_started = Some(now.to_utc() - TimeDelta::minutes(5));
+        assert_eq!(rules.suppress(&stop, now), None);
+
+        let mut prompt = candidate("app", HookEvent::Notification);
+        prompt.task_started = Some(now.to_utc());
+        assert_eq!(rules.suppress(&prompt, now), None);
+    }
+
+    #[test]
+    fn mutes_expire_and_can_be_lifted() {
+        let dir = TempDir::new().unwrap();
+        let rules = rules(&dir, "");
+        let path = dir.path().join("mutes.json");
+        let stop = candidate("app", HookEvent::Stop);
+        let now = Local::now();
+
+        mute(&path, "app", Some(now.to_utc() + TimeDelta::hours(1))).unwrap();
+        assert!(rules.suppress(&stop, now).is_some());
+        assert_eq!(rules.suppress(&stop, now + TimeDelta::hours(2)), None);
+
+        mute(&path, "app", None).unwrap();
+        assert!(rules.suppress(&stop, now + TimeDelta::days(30)).is_some());
+        assert!(unmute(&path, "app").unwrap());
+        assert!(!unmute(&path, "app").unwrap());
+        assert_eq!(rules.suppress(&stop, now), None);
+    }
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(parse_duration("90s").unwrap(), TimeDelta::seconds(90));
+        assert_eq!(parse_duration("30m").unwrap(), TimeDelta::minutes(30));
+        assert_eq!(parse_duration("1h").unwrap(), TimeDelta::hours(1));
+        assert_eq!(parse_duration("2").unwrap(), TimeDelta::hours(2));
+        assert_eq!(parse_duration("2d").unwrap(), TimeDelta::days(2));
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("1w").is_err());
+    }
+
+    #[test]
+    fn out_of_range_durations_are_errors() {
+        assert!(parse_duration("99999999999999d").is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+        let long = parse_duration("99999999d").unwrap();
+        assert!(offset(Utc::now(), long).is_err());
+        assert!(offset(Utc::now(), -long).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{bind, lifecycle};
+use crate::config::AppConfig;
+use crate::user_config::UserConfig;
+
+const SOCKET_UNIT: &str = "ai-pod.socket";
+const SERVICE_UNIT: &str = "ai-pod.service";
+
+/// systemd user unit directory (`~/.config/systemd/user`).
+fn unit_dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("systemd").join("user"))
+}
+
+/// Socket unit listening on `addrs`. `FreeBind` lets it listen on bridge addresses
+/// that only appear once a container network is up.
+fn socket_unit(addrs: &[IpAddr], port: u16) -> String {
+    let mut unit =
+        String::from("[Unit]\nDescription=ai-pod notification server socket\n\n[Socket]\n");
+    for ip in addrs {
+        unit.push_str(&format!("ListenStream={}\n", SocketAddr::new(*ip, port)));
+    }
+    unit.push_str("FreeBind=true\n\n[Install]\nWantedBy=sockets.target\n");
+    unit
+}
+
+fn service_unit(exe: &Path, port: u16) -> String {
+    let exe = exe.to_string_lossy();
+    let exe = if exe.contains(char::is_whitespace) {
+        format!("\"{}\"", exe)
+    } else {
+        exe.into_owned()
+    };
+    format!(
+        "[Unit]\n\
+         Description=ai-pod notification server\n\
+         Requires={SOCKET_UNIT}\n\
+         After={SOCKET_UNIT}\n\n\
+         [Service]\n\
+         ExecStart={exe} --notify-port {port} serve-notifications\n\
+         Restart=on-failure\n"
+    )
+}
+
+/// Port of the installed socket unit in `dir`.
+fn installed_port_in(dir: &Path) -> Option<u16> {
+    let unit = std::fs::read_to_string(dir.join(SOCKET_UNIT)).ok()?;
+    unit.lines()
+        .filter_map(|line| line.strip_prefix("ListenStream="))
+        .find_map(|addr| addr.trim().parse::<SocketAddr>().ok())
+        .map(|addr| addr.port())
+}
+
+/// Whether the server on `port` is run by the installed systemd unit rather than
+/// spawned by `ai-pod`.
+pub fn manages_port(port: u16) -> bool {
+    unit_dir().and_then(|dir| installed_port_in(&dir)) == Some(port)
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run systemctl")?;
+    if !status.success() {
+        anyhow::bail!("`systemctl --user {}` failed", args.join(" "));
+    }
+    Ok(())
+}
+
+/// `active`, `inactive`, `failed`, ... for the socket unit.
+pub fn socket_state() -> String {
+    Command::new("systemctl")
+        .args(["--user", "is-active", SOCKET_UNIT])
+        .stderr(Stdio::null())
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+/// Makes sure the socket listens; systemd starts the server on the first connection.
+pub fn ensure_active() -> Result<()> {
+    if socket_state() == "active" {
+        return Ok(());
+    }
+    systemctl(&["start", SOCKET_UNIT])
+}
+
+/// Stops the server; the socket stays, so the next connection starts it again.
+pub fn stop() -> Result<()> {
+    systemctl(&["stop", SERVICE_UNIT])?;
+    println!("{}", "Notification server stopped.".green());
+    Ok(())
+}
+
+/// Restarts the server, e.g. to pick up an upgraded executable.
+pub fn restart() -> Result<()> {
+    systemctl(&["restart", SERVICE_UNIT])
+}
+
+/// `ai-pod server install`.
+pub fn install(config: &AppConfig, port: u16) -> Result<()> {
+    let dir = unit_dir().context("Could not determine the systemd user unit directory")?;
+    if systemctl(&["show-environment"]).is_err() {
+        anyhow::bail!(
+            "No systemd user instance is available; `ai-pod` keeps starting the server on demand."
+        );
+    }
+    let user_config = UserConfig::load(&config.config_file)?;
+    let addrs = bind::bind_addrs(&user_config.server);
+    let exe = std::env::current_exe().context("Failed to get current executable path")?;
+
+    // The units take over the port from a server `ai-pod` spawned itself.
+    if lifecycle::running_port(config, port).is_some() {
+        lifecycle::stop_spawned(&config.pid_file)?;
+    }
+    let _ = systemctl(&["stop", SERVICE_UNIT]);
+
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    std::fs::write(dir.join(SOCKET_UNIT), socket_unit(&addrs, port))?;
+    std::fs::write(dir.join(SERVICE_UNIT), service_unit(&exe, port))?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", SOCKET_UNIT])?;
+    systemctl(&["restart", SOCKET_UNIT])?;
+
+    println!(
+        "{} {}",
+        "Installed:".green().bold(),
+        dir.join(SOCKET_UNIT).display()
+    );
+    let list: Vec<String> = addrs
+        .iter()
+        .map(|ip| SocketAddr::new(*ip, port).to_string())
+        .collect();
+    println!(
+        "systemd listens on {} and starts the server on the first connection.",
+        list.join(", ")
+    );
+    Ok(())
+}
+
+/// `ai-pod server uninstall`.
+pub fn uninstall() -> Result<()> {
+    let dir = unit_dir().context("Could not determine the systemd user unit directory")?;
+    if !dir.join(SOCKET_UNIT).exists() && !dir.join(SERVICE_UNIT).exists() {
+        println!(
+            "{}",
+            "The notification server is not installed as a service.".yellow()
+        );
+        return Ok(());
+    }
+    let _ = systemctl(&["disable", "--now", SOCKET_UNIT]);
+    let _ = systemctl(&["stop", SERVICE_UNIT]);
+    for unit in [SOCKET_UNIT, SERVICE_UNIT] {
+        let path = dir.join(unit);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+    systemctl(&["daemon-reload"])?;
+    println!(
+        "{} `ai-pod` starts the server on demand again.",
+        "Uninstalled.".green()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn socket_unit_listens_on_every_address() {
+        let addrs = ["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        let unit = socket_unit(&addrs, 9876);
+        assert!(unit.contains("ListenStream=127.0.0.1:9876\n"));
+        assert!(unit.contains("ListenStream=[::1]:9876\n"));
+        assert!(unit.contains("FreeBind=true"));
+        assert!(unit.contains("WantedBy=sockets.target"));
+    }
+
+    #[test]
+    fn service_unit_runs_this_executable_on_the_port() {
+        let unit = service_unit(Path::new("/home/me/.local/bin/ai-pod"), 9000);
+        assert!(unit.contains(
+            "ExecStart=/home/me/.local/bin/ai-pod --notify-port 9000 serve-notifications"
+        ));
+        assert!(unit.contains("Requires=ai-pod.socket"));
+
+        let unit = service_unit(Path::new("/opt/my tools/ai-pod"), 9000);
+        assert!(unit.contains("ExecStart=\"/opt/my tools/ai-pod\" --notify-port"));
+    }
+
+    #[test]
+    fn installed_port_is_read_from_the_socket_unit() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(installed_port_in(dir.path()), None);
+        let addrs = ["127.0.0.1".parse().unwrap()];
+        std::fs::write(dir.path().join(SOCKET_UNIT), socket_unit(&addrs, 9123)).unwrap();
+        assert_eq!(installed_port_in(dir.path()), Some(9123));
+    }
+}
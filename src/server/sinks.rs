@@ -0,0 +1,591 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::dashboard;
+use super::hooks::{HookEvent, Urgency};
+use super::notify;
+use crate::user_config::{NotificationsConfig, SinkConfig};
+
+/// How long a sink may take before it is given up on.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Event kinds that produce notifications, and so can be routed.
+const ROUTABLE: &[HookEvent] = &[
+    HookEvent::Stop,
+    HookEvent::SubagentStop,
+    HookEvent::Notification,
+];
+
+/// A rendered notification, as handed to every sink.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub kind: String,
+    pub project: String,
+    pub container: Option<String>,
+    pub workspace: Option<String>,
+    pub session: Option<String>,
+    pub title: String,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Somewhere notifications are delivered to.
+pub trait NotificationSink: Send + Sync {
+    /// Short description for error messages.
+    fn name(&self) -> String;
+    fn send<'a>(&'a self, notification: &'a Notification) -> SendFuture<'a>;
+}
+
+impl Notification {
+    /// The notification as `AI_POD_*` environment variables for commands.
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let urgency = serde_json::to_value(self.urgency).unwrap_or_default();
+        let mut env = vec![
+            ("AI_POD_EVENT", self.kind.clone()),
+            ("AI_POD_PROJECT", self.project.clone()),
+            (
+                "AI_POD_CONTAINER",
+                self.container.clone().unwrap_or_default(),
+            ),
+            (
+                "AI_POD_WORKSPACE",
+                self.workspace.clone().unwrap_or_default(),
+            ),
+            ("AI_POD_SESSION", self.session.clone().unwrap_or_default()),
+            ("AI_POD_TITLE", self.title.clone()),
+            ("AI_POD_BODY", self.body.clone()),
+            (
+                "AI_POD_URGENCY",
+                urgency.as_str().unwrap_or_default().into(),
+            ),
+        ];
+        if let (Some(workspace), Some(session)) = (&self.workspace, &self.session) {
+            env.push((
+                "AI_POD_ATTACH",
+                dashboard::attach_session_command(workspace, session),
+            ));
+        }
+        env
+    }
+}
+
+/// Native desktop notification. With an open command, it gets "Open" and "Dismiss"
+/// actions where the platform supports them.
+pub struct DesktopSink {
+    open_command: Option<String>,
+}
+
+/// Runs the open command for a notification's session, without waiting for it.
+fn run_open_command(command: &str, env: Vec<(&'static str, String)>) {
+    let spawned = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env)
+        .stdin(std::process::Stdio::null())
+        .spawn();
+    if let Err(e) = spawned {
+        tracing::warn!("Failed to run open command: {e}");
+    }
+}
+
+impl NotificationSink for DesktopSink {
+    fn name(&self) -> String {
+        "desktop".into()
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> SendFuture<'a> {
+        let (title, body, urgency) = (n.title.clone(), n.body.clone(), n.urgency);
+        let open = self.open_command.clone().map(|command| (command, n.env()));
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || match open {
+                Some((command, env)) => {
+                    notify::send_notification_with_open(&title, &body, urgency, move || {
+                        run_open_command(&command, env)
+                    })
+                }
+                None => notify::send_notification(&title, &body, urgency),
+            })
+            .await?;
+            Ok(())
+        })
+    }
+}
+
+/// POSTs the notification as JSON, with optional extra headers.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    headers: BTreeMap<String, String>,
+}
+
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let mut request = self.client.post(&self.url).json(n);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// ntfy publish as JSON to the server root, so titles aren't limited to what fits in
+/// an HTTP header.
+pub struct NtfySink {
+    client: reqwest::Client,
+    server: String,
+    topic: String,
+    token: Option<String>,
+}
+
+/// Splits a topic URL such as `https://ntfy.sh/my-topic` into server and topic.
+fn ntfy_topic(url: &str) -> Result<(String, String)> {
+    match url.trim_end_matches('/').rsplit_once('/') {
+        Some((server, topic)) if server.contains("://") && !topic.is_empty() => {
+            Ok((server.to_string(), topic.to_string()))
+        }
+        _ => anyhow::bail!(
+            "ntfy url '{}' must name a topic, e.g. https://ntfy.sh/my-topic",
+            url
+        ),
+    }
+}
+
+fn ntfy_priority(urgency: Urgency) -> u8 {
+    match urgency {
+        Urgency::Low => 2,
+        Urgency::Normal => 3,
+        Urgency::Critical => 5,
+    }
+}
+
+impl NotificationSink for NtfySink {
+    fn name(&self) -> String {
+        format!("ntfy {}/{}", self.server, self.topic)
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let mut request = self.client.post(&self.server).json(&json!({
+                "topic": self.topic,
+                "title": n.title,
+                "message": n.body,
+                "priority": ntfy_priority(n.urgency),
+                "tags": [n.project],
+            }));
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Slack incoming webhook; Mattermost and Rocket.Chat accept the same payload.
+pub struct SlackSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl NotificationSink for SlackSink {
+    fn name(&self) -> String {
+        format!("slack {}", self.url)
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let text = format!("*{}*\n{}", n.title, n.body);
+            self.client
+                .post(&self.url)
+                .json(&json!({ "text": text }))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Runs `sh -c <command>` with the notification in `AI_POD_*` environment variables.
+pub struct CommandSink {
+    command: String,
+}
+
+impl NotificationSink for CommandSink {
+    fn name(&self) -> String {
+        format!("command `{}`", self.command)
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> SendFuture<'a> {
+        Box::pin(async move {
+            let status = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(&self.command)
+                .envs(n.env())
+                .stdin(std::process::Stdio::null())
+                .kill_on_drop(true)
+                .status()
+                .await
+                .context("Failed to run command")?;
+            if !status.success() {
+                anyhow::bail!("exited with {}", status);
+            }
+            Ok(())
+        })
+    }
+}
+
+struct Route {
+    sink: Arc<dyn NotificationSink>,
+    /// Event kinds the sink receives; all if `None`.
+    events: Option<Vec<String>>,
+}
+
+/// The configured sinks and which events each of them receives.
+#[derive(Default)]
+pub struct Sinks {
+    routes: Vec<Route>,
+}
+
+impl Sinks {
+    /// Builds sinks from `[[notifications.sinks]]`; without any, notifications go to the
+    /// desktop as before.
+    pub fn from_config(config: &NotificationsConfig) -> Result<Self> {
+        let desktop = || DesktopSink {
+            open_command: config.open_command.clone(),
+        };
+        let configs = &config.sinks;
+        if configs.is_empty() {
+            return Ok(Self::default().with(Box::new(desktop()), None));
+        }
+        let client = reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?;
+        let mut sinks = Self::default();
+        for config in configs {
+            let events = config.events().map(<[String]>::to_vec);
+            for event in events.iter().flatten() {
+                if !ROUTABLE.iter().any(|e| e.as_str() == event) {
+                    anyhow::bail!(
+                        "Unknown event '{}' in notification sink (expected one of Stop, SubagentStop, Notification)",
+                        event
+                    );
+                }
+            }
+            let sink: Box<dyn NotificationSink> = match config {
+                SinkConfig::Desktop { .. } => Box::new(desktop()),
+                SinkConfig::Webhook { url, headers, .. } => Box::new(WebhookSink {
+                    client: client.clone(),
+                    url: url.clone(),
+                    headers: headers.clone(),
+                }),
+                SinkConfig::Ntfy { url, token, .. } => {
+                    let (server, topic) = ntfy_topic(url)?;
+                    Box::new(NtfySink {
+                        client: client.clone(),
+                        server,
+                        topic,
+                        token: token.clone(),
+                    })
+                }
+                SinkConfig::Slack { url, .. } => Box::new(SlackSink {
+                    client: client.clone(),
+                    url: url.clone(),
+                }),
+                SinkConfig::Command { command, .. } => Box::new(CommandSink {
+                    command: command.clone(),
+                }),
+            };
+            sinks = sinks.with(sink, events);
+        }
+        Ok(sinks)
+    }
+
+    pub fn with(mut self, sink: Box<dyn NotificationSink>, events: Option<Vec<String>>) -> Self {
+        self.routes.push(Route {
+            sink: Arc::from(sink),
+            events,
+        });
+        self
+    }
+
+    /// Sends to every sink routed for the notification's kind, all at once, so a slow
+    /// sink doesn't hold up the others. Failures are logged and don't stop the other sinks.
+    pub async fn dispatch(&self, notification: &Notification) {
+        let mut sending = tokio::task::JoinSet::new();
+        for route in &self.routes {
+            if route
+                .events
+                .as_ref()
+                .is_some_and(|events| !events.contains(&notification.kind))
+            {
+                continue;
+            }
+            let sink = route.sink.clone();
+            let notification = notification.clone();
+            sending.spawn(async move {
+                let sent = tokio::time::timeout(SEND_TIMEOUT, sink.send(&notification)).await;
+                let error = match sent {
+                    Ok(Ok(())) => return,
+                    Ok(Err(e)) => e.to_string(),
+                    Err(_) => "timed out".to_string(),
+                };
+                tracing::warn!("Sink {} failed: {}", sink.name(), error);
+            });
+        }
+        while sending.join_next().await.is_some() {}
+    }
+}
+
+/// Suppresses repeats of the same event from the same container within a time window.
+pub struct Debounce {
+    window: Duration,
+    last: Mutex<HashMap<String, Instant>>,
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a notification for `key` may be sent at `now`; if so, the window restarts.
+    pub fn allow(&self, key: &str, now: Instant) -> bool {
+        let mut last = self.last.lock().unwrap();
+        if let Some(previous) = last.get(key)
+            && now.duration_since(*previous) < self.window
+        {
+            return false;
+        }
+        last.insert(key.to_string(), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, Uri},
+        routing::post,
+    };
+    use tempfile::TempDir;
+
+    type Received = Arc<Mutex<Vec<(String, HeaderMap, String)>>>;
+
+    /// Local HTTP server standing in for webhook, ntfy and Slack endpoints.
+    async fn stand_in() -> (String, Received) {
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route(
+                "/*path",
+                post(
+                    |State(received): State<Received>,
+                     uri: Uri,
+                     headers: HeaderMap,
+                     body: Bytes| async move {
+                        let body = String::from_utf8_lossy(&body).into_owned();
+                        received
+                            .lock()
+                            .unwrap()
+                            .push((uri.path().to_string(), headers, body));
+                        "ok"
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}", addr), received)
+    }
+
+    fn notification(kind: &str, urgency: Urgency) -> Notification {
+        Notification {
+            kind: kind.into(),
+            project: "demo".into(),
+            container: Some("claude-demo".into()),
+            workspace: Some("/home/me/demo".into()),
+            session: Some("default".into()),
+            title: "demo: permission needed".into(),
+            body: "Bash: cargo test".into(),
+            urgency,
+        }
+    }
+
+    fn sinks(toml: &str) -> Sinks {
+        let config = crate::user_config::UserConfig::parse(toml).unwrap();
+        Sinks::from_config(&config.notifications).unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_sinks_send_their_formats() {
+        let (base, received) = stand_in().await;
+        let sinks = sinks(&format!(
+            r#"
+            [[notifications.sinks]]
+            type = "webhook"
+            url = "{base}/hook"
+            headers = {{ X-Secret = "s3cret" }}
+
+            [[notifications.sinks]]
+            type = "ntfy"
+            url = "{base}/ntfy/topic"
+            token = "tk"
+
+            [[notifications.sinks]]
+            type = "slack"
+            url = "{base}/slack"
+            "#
+        ));
+        sinks
+            .dispatch(&notification("Notification", Urgency::Critical))
+            .await;
+
+        // Sinks are sent to concurrently, so order by endpoint.
+        let mut received = received.lock().unwrap().clone();
+        received.sort_by(|a, b| a.0.cmp(&b.0));
+        let paths: Vec<&str> = received.iter().map(|r| r.0.as_str()).collect();
+        assert_eq!(paths, vec!["/hook", "/ntfy", "/slack"]);
+
+        let (_, headers, body) = &received[0];
+        assert_eq!(headers["x-secret"], "s3cret");
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["kind"], "Notification");
+        assert_eq!(json["project"], "demo");
+        assert_eq!(json["urgency"], "critical");
+
+        let (_, headers, body) = &received[1];
+        assert_eq!(headers["authorization"], "Bearer tk");
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["topic"], "topic");
+        assert_eq!(json["title"], "demo: permission needed");
+        assert_eq!(json["message"], "Bash: cargo test");
+        assert_eq!(json["priority"], 5);
+        assert_eq!(json["tags"][0], "demo");
+
+        let (_, _, body) = &received[2];
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["text"], "*demo: permission needed*\nBash: cargo test");
+    }
+
+    #[tokio::test]
+    async fn ntfy_sends_non_ascii_titles() {
+        let (base, received) = stand_in().await;
+        let sinks = sinks(&format!(
+            "[[notifications.sinks]]\ntype = \"ntfy\"\nurl = \"{base}/ntfy/topic\""
+        ));
+        let mut n = notification("Stop", Urgency::Normal);
+        n.title = "café: Aufgabe erledigt ✓".into();
+        sinks.dispatch(&n).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let json: serde_json::Value = serde_json::from_str(&received[0].2).unwrap();
+        assert_eq!(json["title"], "café: Aufgabe erledigt ✓");
+    }
+
+    #[test]
+    fn ntfy_urls_must_name_a_topic() {
+        assert_eq!(
+            ntfy_topic("https://ntfy.sh/my-topic/").unwrap(),
+            ("https://ntfy.sh".to_string(), "my-topic".to_string())
+        );
+        assert!(ntfy_topic("https://ntfy.sh").is_err());
+    }
+
+    #[tokio::test]
+    async fn sinks_only_receive_routed_events() {
+        let (base, received) = stand_in().await;
+        let sinks = sinks(&format!(
+            r#"
+            [[notifications.sinks]]
+            type = "slack"
+            url = "{base}/slack"
+            events = ["Notification"]
+            "#
+        ));
+        sinks.dispatch(&notification("Stop", Urgency::Normal)).await;
+        sinks
+            .dispatch(&notification("SubagentStop", Urgency::Low))
+            .await;
+        assert!(received.lock().unwrap().is_empty());
+
+        sinks
+            .dispatch(&notification("Notification", Urgency::Critical))
+            .await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn command_sink_receives_the_notification_in_env() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("out");
+        let sink = CommandSink {
+            command: format!(
+                "printf '%s|%s|%s|%s' \"$AI_POD_EVENT\" \"$AI_POD_PROJECT\" \"$AI_POD_URGENCY\" \"$AI_POD_ATTACH\" > {}",
+                out.display()
+            ),
+        };
+        sink.send(&notification("Stop", Urgency::Low))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "Stop|demo|low|ai-pod attach --workdir /home/me/demo"
+        );
+
+        let failing = CommandSink {
+            command: "exit 3".into(),
+        };
+        assert!(
+            failing
+                .send(&notification("Stop", Urgency::Low))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn unknown_routed_events_are_rejected() {
+        let config = crate::user_config::UserConfig::parse(
+            "[[notifications.sinks]]\ntype = \"desktop\"\nevents = [\"PreToolUse\"]",
+        )
+        .unwrap();
+        assert!(Sinks::from_config(&config.notifications).is_err());
+        let default = NotificationsConfig::default();
+        assert_eq!(Sinks::from_config(&default).unwrap().routes.len(), 1);
+    }
+
+    #[test]
+    fn debounce_suppresses_repeats_within_the_window() {
+        let debounce = Debounce::new(Duration::from_secs(10));
+        let start = Instant::now();
+        assert!(debounce.allow("claude-a/Stop", start));
+        assert!(!debounce.allow("claude-a/Stop", start + Duration::from_secs(3)));
+        assert!(debounce.allow("claude-b/Stop", start + Duration::from_secs(3)));
+        assert!(debounce.allow("claude-a/Stop", start + Duration::from_secs(11)));
+
+        let disabled = Debounce::new(Duration::ZERO);
+        assert!(disabled.allow("claude-a/Stop", start));
+        assert!(disabled.allow("claude-a/Stop", start));
+    }
+}
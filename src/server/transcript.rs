@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::process::{Command, Stdio};
+
+/// Transcript lines read from the end of the file; enough to cover the last turn.
+const TAIL_LINES: &str = "400";
+
+/// Tools whose `file_path` (or `notebook_path`) is a file Claude changed.
+const EDIT_TOOLS: [&str; 4] = ["Edit", "MultiEdit", "Write", "NotebookEdit"];
+
+/// What happened in the last turn of a Claude session.
+#[derive(Debug, Default, PartialEq)]
+pub struct TurnSummary {
+    pub last_message: Option<String>,
+    pub files_changed: BTreeSet<String>,
+    /// When the prompt that started the turn was sent.
+    pub started: Option<DateTime<Utc>>,
+}
+
+/// A user entry typed by the user, as opposed to one carrying tool results.
+fn is_prompt(entry: &Value) -> bool {
+    match &entry["message"]["content"] {
+        Value::String(_) => true,
+        Value::Array(items) => items.iter().all(|i| i["type"] != "tool_result"),
+        _ => false,
+    }
+}
+
+/// Summarizes the turn after the last user prompt in a JSONL transcript.
+/// Malformed lines (e.g. one cut off by `tail`) are skipped.
+pub fn summarize(jsonl: &str) -> TurnSummary {
+    let mut summary = TurnSummary::default();
+    for entry in jsonl
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+    {
+        match entry["type"].as_str() {
+            Some("user") if is_prompt(&entry) => {
+                summary = TurnSummary {
+                    started: entry["timestamp"].as_str().and_then(|t| t.parse().ok()),
+                    ..TurnSummary::default()
+                }
+            }
+            Some("assistant") => {
+                let Some(items) = entry["message"]["content"].as_array() else {
+                    continue;
+                };
+                for item in items {
+                    match item["type"].as_str() {
+                        Some("text") => {
+                            let text = item["text"].as_str().unwrap_or("").trim();
+                            if !text.is_empty() {
+                                summary.last_message = Some(text.to_string());
+                            }
+                        }
+                        Some("tool_use")
+                            if EDIT_TOOLS.contains(&item["name"].as_str().unwrap_or("")) =>
+                        {
+                            let input = &item["input"];
+                            if let Some(path) = input["file_path"]
+                                .as_str()
+                                .or(input["notebook_path"].as_str())
+                            {
+                                summary.files_changed.insert(path.to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    summary
+}
+
+/// Reads the end of a transcript from inside the container; the file lives in the
+/// container's home volume, which the host cannot read directly.
+pub fn read_tail(container: &str, path: &str) -> Option<String> {
+    let output = Command::new("podman")
+        .args(["exec", container, "tail", "-n", TAIL_LINES, path])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn jsonl(entries: &[Value]) -> String {
+        entries
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn assistant(content: Value) -> Value {
+        json!({"type": "assistant", "message": {"role": "assistant", "content": content}})
+    }
+
+    #[test]
+    fn summarizes_the_last_turn_only() {
+        let transcript = jsonl(&[
+            json!({"type": "user", "message": {"role": "user", "content": "first"}}),
+            assistant(
+                json!([{"type": "tool_use", "name": "Write", "input": {"file_path": "/app/old.rs"}}]),
+            ),
+            json!({"type": "user", "timestamp": "2025-06-01T10:00:00.000Z",
+                   "message": {"role": "user", "content": "second"}}),
+            assistant(json!([
+                {"type": "text", "text": "Let me look."},
+                {"type": "tool_use", "name": "Edit", "input": {"file_path": "/app/a.rs"}}
+            ])),
+            json!({"type": "user", "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "1", "content": "ok"}
+            ]}}),
+            assistant(json!([
+                {"type": "tool_use", "name": "Edit", "input": {"file_path": "/app/a.rs"}},
+                {"type": "tool_use", "name": "Write", "input": {"file_path": "/app/b.rs"}},
+                {"type": "tool_use", "name": "Read", "input": {"file_path": "/app/c.rs"}}
+            ])),
+            assistant(json!([{"type": "text", "text": "  Fixed the parser.  "}])),
+        ]);
+
+        let summary = summarize(&transcript);
+        assert_eq!(summary.last_message.as_deref(), Some("Fixed the parser."));
+        assert_eq!(
+            summary.files_changed.into_iter().collect::<Vec<_>>(),
+            vec!["/app/a.rs", "/app/b.rs"]
+        );
+        assert_eq!(
+            summary.started.unwrap().to_rfc3339(),
+            "2025-06-01T10:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let transcript = format!(
+            "{{\"type\": \"assis\n{}",
+            assistant(json!([{"type": "text", "text": "done"}]))
+        );
+        assert_eq!(summarize(&transcript).last_message.as_deref(), Some("done"));
+    }
+}
@@ -0,0 +1,132 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use super::auth;
+use super::events::EventLog;
+use crate::container::{DEFAULT_SESSION, SESSION_LABEL, WORKSPACE_LABEL, project_name};
+
+/// A start, stop or removal of an ai-pod session container.
+#[derive(Debug, PartialEq)]
+pub struct ContainerChange {
+    pub kind: &'static str,
+    pub container: String,
+    pub workspace: String,
+    pub session: String,
+}
+
+impl ContainerChange {
+    fn project(&self) -> String {
+        project_name(Path::new(&self.workspace))
+    }
+
+    fn title(&self) -> String {
+        let what = match self.kind {
+            "ContainerStarted" => "container started",
+            "ContainerStopped" => "container stopped",
+            _ => "container removed",
+        };
+        format!("{}: {}", self.project(), what)
+    }
+}
+
+/// Parses one line of `podman events --format json`. Only session containers carry
+/// the workspace label, so helper containers (init, sync) are ignored.
+pub fn parse_podman_event(line: &str) -> Option<ContainerChange> {
+    let event: Value = serde_json::from_str(line).ok()?;
+    if event["Type"] != "container" {
+        return None;
+    }
+    let kind = match event["Status"].as_str()? {
+        "start" => "ContainerStarted",
+        "died" => "ContainerStopped",
+        "remove" => "ContainerRemoved",
+        _ => return None,
+    };
+    let attributes = &event["Attributes"];
+    Some(ContainerChange {
+        kind,
+        container: event["Name"].as_str()?.to_string(),
+        workspace: attributes[WORKSPACE_LABEL].as_str()?.to_string(),
+        session: attributes[SESSION_LABEL]
+            .as_str()
+            .unwrap_or(DEFAULT_SESSION)
+            .to_string(),
+    })
+}
+
+/// Broadcasts container lifecycle changes from `podman events` until podman exits or
+/// the task is dropped with the server, and revokes the session tokens of removed
+/// containers. Without podman the server simply has no lifecycle events.
+pub async fn watch_containers(events: Arc<EventLog>, token_dir: PathBuf) {
+    let child = Command::new("podman")
+        .args(["events", "--format", "json", "--filter", "type=container"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            tracing::warn!("Not watching containers: {e}");
+            return;
+        }
+    };
+    let Some(stdout) = child.stdout.take() else {
+        return;
+    };
+
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(change) = parse_podman_event(&line) {
+            if change.kind == "ContainerRemoved" {
+                auth::revoke(&token_dir, &change.container);
+            }
+            let body = format!("{} (session {})", change.container, change.session);
+            events.broadcast(
+                change.kind,
+                &change.project(),
+                Some(&change.container),
+                &change.title(),
+                &body,
+            );
+        }
+    }
+    let _ = child.wait().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_session_container_events() {
+        let line = r#"{"ID":"abc","Image":"ai-pod-app","Name":"claude-1234-review","Status":"start","Time":"2026-01-01T10:00:00Z","Type":"container","Attributes":{"ai-pod.workspace":"/home/me/app","ai-pod.session":"review","image":"ai-pod-app"}}"#;
+        let change = parse_podman_event(line).unwrap();
+        assert_eq!(
+            change,
+            ContainerChange {
+                kind: "ContainerStarted",
+                container: "claude-1234-review".into(),
+                workspace: "/home/me/app".into(),
+                session: "review".into(),
+            }
+        );
+        assert_eq!(change.title(), "app: container started");
+    }
+
+    #[test]
+    fn ignores_other_containers_and_statuses() {
+        let helper =
+            r#"{"Name":"claude-1234-sync","Status":"start","Type":"container","Attributes":{}}"#;
+        assert!(parse_podman_event(helper).is_none());
+
+        let exec = r#"{"Name":"claude-1234","Status":"exec","Type":"container","Attributes":{"ai-pod.workspace":"/app"}}"#;
+        assert!(parse_podman_event(exec).is_none());
+
+        assert!(parse_podman_event("not json").is_none());
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Value, json};
+
+use crate::project::ClaudeConfig;
+use crate::server::hooks::HookEvent;
+
+/// Hook command registered for every event: forwards the hook's JSON payload (stdin) to
+/// the notification server, authenticated with the container's session token. It never
+/// fails or blocks Claude for long.
+const HOOK_COMMAND: &str = "curl -sf --max-time 2 -X POST -H 'Content-Type: application/json' \
+                            -H \"Authorization: Bearer $NOTIFY_TOKEN\" \
+                            --data-binary @- \"$NOTIFY_URL\" >/dev/null 2>&1 || true";
+
+/// Hook groups ai-pod registers, keyed by Claude hook event.
+fn ai_pod_hooks() -> Vec<(&'static str, Value)> {
+    HookEvent::ALL
+        .iter()
+        .map(|event| {
+            let group = json!({
+                "matcher": "*",
+                "hooks": [{ "type": "command", "command": HOOK_COMMAND }]
+            });
+            (event.as_str(), group)
+        })
+        .collect()
+}
+
+/// Parses host `settings.json`, which must be a JSON object.
+pub fn parse_host_settings(raw: &str) -> Result<Value> {
+    let value: Value = serde_json::from_str(raw).context("settings.json is not valid JSON")?;
+    if !value.is_object() {
+        anyhow::bail!("settings.json is not a JSON object");
+    }
+    Ok(value)
+}
+
+/// Returns `obj[key]` as an object, replacing a value of another type.
+/// The replaced key is reported in `warnings`.
+fn object_entry<'a>(
+    obj: &'a mut Map<String, Value>,
+    key: &str,
+    path: &str,
+    warnings: &mut Vec<String>,
+) -> &'a mut Map<String, Value> {
+    let entry = obj.entry(key).or_insert_with(|| json!({}));
+    if !entry.is_object() {
+        warnings.push(format!("{} is not an object and was replaced", path));
+        *entry = json!({});
+    }
+    entry.as_object_mut().expect("just ensured an object")
+}
+
+/// Same as [`object_entry`] for arrays.
+fn array_entry<'a>(
+    obj: &'a mut Map<String, Value>,
+    key: &str,
+    path: &str,
+    warnings: &mut Vec<String>,
+) -> &'a mut Vec<Value> {
+    let entry = obj.entry(key).or_insert_with(|| json!([]));
+    if !entry.is_array() {
+        warnings.push(format!("{} is not an array and was replaced", path));
+        *entry = json!([]);
+    }
+    entry.as_array_mut().expect("just ensured an array")
+}
+
+/// Appends `items` to `list`, skipping values already present (and duplicates within it).
+fn append_unique(list: &mut Vec<Value>, items: impl IntoIterator<Item = Value>) {
+    let mut merged: Vec<Value> = Vec::with_capacity(list.len());
+    for item in list.drain(..).chain(items) {
+        if !merged.contains(&item) {
+            merged.push(item);
+        }
+    }
+    *list = merged;
+}
+
+/// Adds ai-pod's hook groups after the user's own, without duplicating groups that are
+/// already there (e.g. from an earlier sync).
+fn merge_hooks(settings: &mut Map<String, Value>, warnings: &mut Vec<String>) {
+    let hooks = object_entry(settings, "hooks", "hooks", warnings);
+    for (event, group) in ai_pod_hooks() {
+        let path = format!("hooks.{}", event);
+        append_unique(array_entry(hooks, event, &path, warnings), [group]);
+    }
+}
+
+fn merge_permissions(
+    settings: &mut Map<String, Value>,
+    claude: &ClaudeConfig,
+    warnings: &mut Vec<String>,
+) {
+    let rules = &claude.permissions;
+    if rules.allow.is_empty() && rules.deny.is_empty() {
+        return;
+    }
+    let permissions = object_entry(settings, "permissions", "permissions", warnings);
+    for (key, items) in [("allow", &rules.allow), ("deny", &rules.deny)] {
+        if items.is_empty() {
+            continue;
+        }
+        let path = format!("permissions.{}", key);
+        let list = array_entry(permissions, key, &path, warnings);
+        append_unique(list, items.iter().map(|i| Value::String(i.clone())));
+    }
+}
+
+/// Project env vars win over host ones with the same name.
+fn merge_env(settings: &mut Map<String, Value>, claude: &ClaudeConfig, warnings: &mut Vec<String>) {
+    if claude.env.is_empty() {
+        return;
+    }
+    let env = object_entry(settings, "env", "env", warnings);
+    for (key, value) in &claude.env {
+        env.insert(key.clone(), Value::String(value.clone()));
+    }
+}
+
+/// Builds the container's `settings.json` from the host's and the project's `[claude]`
+/// section. Returns the settings and warnings about values that had to be replaced.
+pub fn runtime_settings(host: Value, claude: &ClaudeConfig) -> (Value, Vec<String>) {
+    let mut warnings = Vec::new();
+    let mut settings = match host {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    };
+    merge_hooks(&mut settings, &mut warnings);
+    merge_permissions(&mut settings, claude, &mut warnings);
+    merge_env(&mut settings, claude, &mut warnings);
+    (Value::Object(settings), warnings)
+}
+
+/// Adds the project's MCP servers to the contents of `~/.claude.json`, replacing servers
+/// of the same name. Returns the names whose definition changed.
+pub fn merge_mcp_servers(
+    claude_json: &mut Value,
+    claude: &ClaudeConfig,
+    warnings: &mut Vec<String>,
+) -> Vec<String> {
+    if !claude_json.is_object() {
+        warnings.push(".claude.json is not an object and was replaced".into());
+        *claude_json = json!({});
+    }
+    let obj = claude_json.as_object_mut().expect("just ensured an object");
+    let servers = object_entry(obj, "mcpServers", "mcpServers", warnings);
+
+    let mut changed = Vec::new();
+    for (name, definition) in &claude.mcp_servers {
+        if servers.get(name) != Some(definition) {
+            servers.insert(name.clone(), definition.clone());
+            changed.push(name.clone());
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::ProjectConfig;
+
+    fn claude(toml: &str) -> ClaudeConfig {
+        ProjectConfig::parse(toml).unwrap().claude
+    }
+
+    #[test]
+    fn user_stop_hooks_are_kept() {
+        let host = json!({"hooks": {"Stop": [
+            {"matcher": "", "hooks": [{"type": "command", "command": "say done"}]}
+        ]}});
+        let (settings, warnings) = runtime_settings(host, &ClaudeConfig::default());
+        let stop = settings["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 2);
+        assert_eq!(stop[0]["hooks"][0]["command"], "say done");
+        assert_eq!(stop[1]["hooks"][0]["command"], HOOK_COMMAND);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn all_hook_events_are_registered() {
+        let (settings, _) = runtime_settings(json!({}), &ClaudeConfig::default());
+        for event in ["Stop", "SubagentStop", "Notification", "PreToolUse"] {
+            let groups = settings["hooks"][event].as_array().unwrap();
+            assert_eq!(groups[0]["hooks"][0]["command"], HOOK_COMMAND, "{}", event);
+        }
+    }
+
+    #[test]
+    fn ai_pod_hooks_are_not_duplicated() {
+        let (once, _) = runtime_settings(json!({}), &ClaudeConfig::default());
+        let (twice, _) = runtime_settings(once.clone(), &ClaudeConfig::default());
+        assert_eq!(once, twice);
+        assert_eq!(twice["hooks"]["Stop"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn permissions_are_merged_and_deduped() {
+        let host = json!({"permissions": {"allow": ["Read", "Bash(ls)"], "defaultMode": "plan"}});
+        let project = claude(
+            r#"
+            [claude.permissions]
+            allow = ["Bash(ls)", "Bash(cargo test:*)"]
+            deny = ["Read(./.env)"]
+            "#,
+        );
+        let (settings, _) = runtime_settings(host, &project);
+        assert_eq!(
+            settings["permissions"],
+            json!({
+                "allow": ["Read", "Bash(ls)", "Bash(cargo test:*)"],
+                "deny": ["Read(./.env)"],
+                "defaultMode": "plan"
+            })
+        );
+    }
+
+    #[test]
+    fn project_env_overrides_host_env() {
+        let host = json!({"env": {"A": "host", "B": "host"}});
+        let project = claude("[claude.env]\nB = \"project\"\nC = \"project\"");
+        let (settings, _) = runtime_settings(host, &project);
+        assert_eq!(
+            settings["env"],
+            json!({"A": "host", "B": "project", "C": "project"})
+        );
+    }
+
+    #[test]
+    fn malformed_sections_are_replaced_with_a_warning() {
+        let host = json!({"hooks": "nope"});
+        let (settings, warnings) = runtime_settings(host, &ClaudeConfig::default());
+        assert!(settings["hooks"]["Stop"].is_array());
+        assert_eq!(warnings, vec!["hooks is not an object and was replaced"]);
+    }
+
+    #[test]
+    fn parse_host_settings_rejects_invalid_json() {
+        assert!(parse_host_settings("{\"theme\": ").is_err());
+        assert!(parse_host_settings("[]").is_err());
+        assert!(parse_host_settings("{}").is_ok());
+    }
+
+    #[test]
+    fn mcp_servers_are_added_and_reported() {
+        let project = claude("[claude.mcp_servers.db]\ncommand = \"pg-mcp\"");
+        let mut claude_json = json!({"mcpServers": {"other": {"command": "x"}}, "theme": "dark"});
+        let mut warnings = Vec::new();
+
+        let changed = merge_mcp_servers(&mut claude_json, &project, &mut warnings);
+        assert_eq!(changed, vec!["db"]);
+        assert_eq!(claude_json["mcpServers"]["db"]["command"], "pg-mcp");
+        assert_eq!(claude_json["mcpServers"]["other"]["command"], "x");
+
+        let changed = merge_mcp_servers(&mut claude_json, &project, &mut warnings);
+        assert!(changed.is_empty());
+    }
+}
@@ -4,16 +4,15 @@ const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const RELEASES_URL: &str = "https://api.github.com/repos/farbenmeer/ai-pod/releases/latest";
 
 pub async fn check_for_update() {
-    if let Ok(latest) = fetch_latest_version().await {
-        if is_newer(&latest, CURRENT_VERSION) {
-            println!(
-                "{} {} → {} — {}",
-                "Update available:".yellow().bold(),
-                CURRENT_VERSION.dimmed(),
-                latest.green().bold(),
-                "https://github.com/farbenmeer/ai-pod/releases/latest"
-            );
-        }
+    if let Ok(latest) = fetch_latest_version().await
+        && is_newer(&latest, CURRENT_VERSION)
+    {
+        println!(
+            "{} {} → {} — https://github.com/farbenmeer/ai-pod/releases/latest",
+            "Update available:".yellow().bold(),
+            CURRENT_VERSION.dimmed(),
+            latest.green().bold(),
+        );
     }
 }
 
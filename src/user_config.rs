@@ -0,0 +1,201 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// User-wide settings read from `~/.ai-pod/config.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserConfig {
+    pub server: ServerConfig,
+    pub notifications: NotificationsConfig,
+}
+
+/// The `[server]` section: how the notification server is exposed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Addresses to listen on. Unset means loopback plus detected container bridges.
+    pub bind: Option<Vec<IpAddr>>,
+    /// Size in MiB at which `server.log` is rotated (default 5).
+    pub log_max_mb: Option<u64>,
+    /// Rotated logs to keep (default 3).
+    pub log_files: Option<usize>,
+}
+
+/// The `[notifications]` section.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// Where notifications go. Without any, they are shown on the desktop.
+    pub sinks: Vec<SinkConfig>,
+    /// Shell command run by the "Open" action of desktop notifications, with the
+    /// session in `AI_POD_*` variables (e.g. `AI_POD_ATTACH`).
+    pub open_command: Option<String>,
+    /// Repeated `Stop`/`SubagentStop` events of a container within this many seconds
+    /// only notify once. Defaults to 10; 0 disables it.
+    pub debounce_secs: Option<u64>,
+    /// Local time range without notifications, e.g. `"22:00-07:00"`.
+    pub quiet_hours: Option<String>,
+    /// Projects (workspace directory names) that never notify.
+    pub muted: Vec<String>,
+    /// `Stop` only notifies for tasks that ran at least this long.
+    pub min_task_secs: Option<u64>,
+    /// Skip notifications while the session's terminal is focused. Defaults to true.
+    pub suppress_when_focused: Option<bool>,
+    /// Critical notifications (permission prompts) ignore quiet hours and mutes.
+    /// Defaults to true.
+    pub critical_bypasses_quiet: Option<bool>,
+}
+
+/// One `[[notifications.sinks]]` entry. `events` limits the sink to some event kinds
+/// (`Stop`, `SubagentStop`, `Notification`); it receives all of them when unset.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SinkConfig {
+    Desktop {
+        events: Option<Vec<String>>,
+    },
+    /// POSTs the notification as JSON.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        events: Option<Vec<String>>,
+    },
+    /// Publishes to an ntfy topic URL, e.g. `https://ntfy.sh/my-topic`.
+    Ntfy {
+        url: String,
+        /// Access token for protected topics.
+        token: Option<String>,
+        events: Option<Vec<String>>,
+    },
+    /// Slack or Mattermost incoming webhook.
+    Slack {
+        url: String,
+        events: Option<Vec<String>>,
+    },
+    /// Runs a shell command with the notification in `AI_POD_*` variables.
+    Command {
+        command: String,
+        events: Option<Vec<String>>,
+    },
+}
+
+impl SinkConfig {
+    pub fn events(&self) -> Option<&[String]> {
+        match self {
+            Self::Desktop { events }
+            | Self::Webhook { events, .. }
+            | Self::Ntfy { events, .. }
+            | Self::Slack { events, .. }
+            | Self::Command { events, .. } => events.as_deref(),
+        }
+    }
+}
+
+impl UserConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&raw).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config_uses_defaults() {
+        let config = UserConfig::parse("").unwrap();
+        assert!(config.server.bind.is_none());
+    }
+
+    #[test]
+    fn bind_addresses_are_parsed() {
+        let config = UserConfig::parse("[server]\nbind = [\"127.0.0.1\", \"::1\"]").unwrap();
+        let bind = config.server.bind.unwrap();
+        assert_eq!(bind.len(), 2);
+        assert!(bind[1].is_ipv6());
+
+        let config = UserConfig::parse("[server]\nlog_max_mb = 1\nlog_files = 5").unwrap();
+        assert_eq!(config.server.log_max_mb, Some(1));
+        assert_eq!(config.server.log_files, Some(5));
+    }
+
+    #[test]
+    fn sinks_are_parsed_by_type() {
+        let config = UserConfig::parse(
+            r#"
+            [[notifications.sinks]]
+            type = "desktop"
+
+            [[notifications.sinks]]
+            type = "ntfy"
+            url = "https://ntfy.sh/claude"
+            events = ["Notification"]
+
+            [[notifications.sinks]]
+            type = "webhook"
+            url = "https://example.com/hook"
+            headers = { Authorization = "Bearer x" }
+            "#,
+        )
+        .unwrap();
+        let sinks = &config.notifications.sinks;
+        assert_eq!(sinks.len(), 3);
+        assert_eq!(sinks[0], SinkConfig::Desktop { events: None });
+        assert_eq!(sinks[1].events(), Some(&["Notification".to_string()][..]));
+        match &sinks[2] {
+            SinkConfig::Webhook { headers, .. } => assert_eq!(headers["Authorization"], "Bearer x"),
+            other => panic!("unexpected sink {:?}", other),
+        }
+    }
+
+    #[test]
+    fn notification_options_are_parsed() {
+        let config = UserConfig::parse(
+            "[notifications]\nopen_command = \"tmux select-window -t $AI_POD_PROJECT\"\ndebounce_secs = 0",
+        )
+        .unwrap();
+        let notifications = config.notifications;
+        assert!(notifications.open_command.unwrap().starts_with("tmux"));
+        assert_eq!(notifications.debounce_secs, Some(0));
+
+        let config = UserConfig::parse(
+            "[notifications]\nquiet_hours = \"22:00-07:00\"\nmuted = [\"scratch\"]\nmin_task_secs = 60",
+        )
+        .unwrap();
+        let notifications = config.notifications;
+        assert_eq!(notifications.quiet_hours.as_deref(), Some("22:00-07:00"));
+        assert_eq!(notifications.muted, vec!["scratch"]);
+        assert_eq!(notifications.min_task_secs, Some(60));
+        assert!(notifications.suppress_when_focused.is_none());
+    }
+
+    #[test]
+    fn unknown_sink_types_and_fields_are_rejected() {
+        assert!(UserConfig::parse("[[notifications.sinks]]\ntype = \"pager\"").is_err());
+        assert!(
+            UserConfig::parse(
+                "[[notifications.sinks]]\ntype = \"slack\"\nurl = \"u\"\nchannel = \"x\""
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn invalid_addresses_and_unknown_keys_are_rejected() {
+        assert!(UserConfig::parse("[server]\nbind = [\"localhost\"]").is_err());
+        assert!(UserConfig::parse("[server]\nport = 1").is_err());
+    }
+}
@@ -0,0 +1,513 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::AppConfig;
+use crate::container;
+
+/// Automatic snapshots kept per volume. Manual snapshots are never pruned.
+const AUTO_SNAPSHOTS_KEPT: usize = 5;
+
+/// Why a snapshot was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotReason {
+    /// `ai-pod volume snapshot`.
+    Manual,
+    /// Before `ai-pod clean` deleted the volume.
+    Clean,
+    /// Before `--rebuild` replaced the container.
+    Rebuild,
+    /// Before `ai-pod volume restore` replaced the volume contents.
+    Restore,
+    /// Before `ai-pod volume import` replaced the volume contents.
+    Import,
+}
+
+impl SnapshotReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Clean => "clean",
+            Self::Rebuild => "rebuild",
+            Self::Restore => "restore",
+            Self::Import => "import",
+        }
+    }
+
+    fn is_automatic(self) -> bool {
+        self != Self::Manual
+    }
+}
+
+/// Metadata stored as `<id>.json` next to the `<id>.tar` archive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    pub id: String,
+    pub volume: String,
+    pub workspace: PathBuf,
+    pub reason: SnapshotReason,
+    pub created_at: DateTime<Utc>,
+    /// Archive size in bytes.
+    pub size: u64,
+}
+
+#[derive(Debug)]
+pub struct Snapshot {
+    pub meta: SnapshotMeta,
+    pub archive: PathBuf,
+}
+
+/// Snapshots of a volume live in `~/.ai-pod/snapshots/<volume>/`.
+fn volume_dir(config: &AppConfig, volume: &str) -> PathBuf {
+    config.snapshot_dir.join(volume)
+}
+
+/// Timestamp-based id, made unique within `dir` if several snapshots share a second.
+fn new_snapshot_id(dir: &Path, now: DateTime<Utc>) -> String {
+    let base = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut id = base.clone();
+    let mut n = 1;
+    while dir.join(format!("{}.json", id)).exists() || dir.join(format!("{}.tar", id)).exists() {
+        n += 1;
+        id = format!("{}-{}", base, n);
+    }
+    id
+}
+
+/// Snapshots in `dir`, oldest first. Entries with missing archives or unreadable
+/// metadata are ignored.
+fn read_snapshots(dir: &Path) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(dir).context("Failed to read snapshot directory")? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Ok(raw) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(meta) = serde_json::from_str::<SnapshotMeta>(&raw) else {
+            continue;
+        };
+        let archive = path.with_extension("tar");
+        if archive.exists() {
+            snapshots.push(Snapshot { meta, archive });
+        }
+    }
+    snapshots.sort_by(|a, b| (a.meta.created_at, &a.meta.id).cmp(&(b.meta.created_at, &b.meta.id)));
+    Ok(snapshots)
+}
+
+/// Deletes all but the newest `keep` automatic snapshots in `dir`, never the one whose
+/// archive is `protected`. Returns the removed ids.
+fn prune_automatic(dir: &Path, keep: usize, protected: Option<&Path>) -> Result<Vec<String>> {
+    let protected = protected.and_then(|p| p.canonicalize().ok());
+    let automatic: Vec<Snapshot> = read_snapshots(dir)?
+        .into_iter()
+        .filter(|s| s.meta.reason.is_automatic())
+        .filter(|s| protected.is_none() || s.archive.canonicalize().ok() != protected)
+        .collect();
+    let excess = automatic.len().saturating_sub(keep);
+
+    let mut removed = Vec::new();
+    for snapshot in automatic.into_iter().take(excess) {
+        std::fs::remove_file(&snapshot.archive)?;
+        std::fs::remove_file(snapshot.archive.with_extension("json"))?;
+        removed.push(snapshot.meta.id);
+    }
+    Ok(removed)
+}
+
+/// Picks a snapshot by id, or the newest one for `latest`.
+fn find_snapshot(snapshots: Vec<Snapshot>, reference: &str) -> Option<Snapshot> {
+    if reference == "latest" {
+        return snapshots.into_iter().last();
+    }
+    snapshots.into_iter().find(|s| s.meta.id == reference)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn ensure_volume_exists(volume: &str) -> Result<()> {
+    if !container::volume_exists(volume)? {
+        anyhow::bail!("Volume {} does not exist", volume);
+    }
+    Ok(())
+}
+
+/// Refuses to touch a volume that a container still has mounted.
+fn ensure_volume_unused(volume: &str) -> Result<()> {
+    let output = Command::new("podman")
+        .args([
+            "ps",
+            "-a",
+            "--filter",
+            &format!("volume={}", volume),
+            "--format",
+            "{{.Names}}",
+        ])
+        .output()
+        .context("Failed to list containers using volume")?;
+    let users = String::from_utf8_lossy(&output.stdout);
+    let users: Vec<&str> = users.lines().filter(|l| !l.is_empty()).collect();
+    if !users.is_empty() {
+        anyhow::bail!(
+            "Volume {} is in use by {}; stop the container first",
+            volume,
+            users.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Replaces the contents of `volume` with `archive`, recreating the volume so that
+/// files missing from the archive do not survive.
+fn replace_volume_contents(volume: &str, archive: &Path) -> Result<()> {
+    if container::volume_exists(volume)? {
+        let status = Command::new("podman")
+            .args(["volume", "rm", volume])
+            .status()
+            .context("Failed to remove volume")?;
+        if !status.success() {
+            anyhow::bail!("Failed to remove volume {}", volume);
+        }
+    }
+    let status = Command::new("podman")
+        .args(["volume", "create", volume])
+        .status()
+        .context("Failed to create volume")?;
+    if !status.success() {
+        anyhow::bail!("Failed to create volume {}", volume);
+    }
+    let status = Command::new("podman")
+        .args(["volume", "import", volume])
+        .arg(archive)
+        .status()
+        .context("Failed to import volume")?;
+    if !status.success() {
+        anyhow::bail!("podman volume import failed for {}", volume);
+    }
+    Ok(())
+}
+
+/// Exports `volume` into a new snapshot. Automatic snapshots prune older automatic ones.
+pub fn snapshot(
+    config: &AppConfig,
+    workspace: &Path,
+    volume: &str,
+    reason: SnapshotReason,
+) -> Result<Snapshot> {
+    take_snapshot(config, workspace, volume, reason, None)
+}
+
+/// [`snapshot`], keeping the snapshot with the `protected` archive out of pruning.
+fn take_snapshot(
+    config: &AppConfig,
+    workspace: &Path,
+    volume: &str,
+    reason: SnapshotReason,
+    protected: Option<&Path>,
+) -> Result<Snapshot> {
+    ensure_volume_exists(volume)?;
+    let dir = volume_dir(config, volume);
+    std::fs::create_dir_all(&dir).context("Failed to create snapshot directory")?;
+
+    let now = Utc::now();
+    let id = new_snapshot_id(&dir, now);
+    let archive = dir.join(format!("{}.tar", id));
+    let status = Command::new("podman")
+        .args(["volume", "export", "--output"])
+        .arg(&archive)
+        .arg(volume)
+        .status()
+        .context("Failed to run podman volume export")?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&archive);
+        anyhow::bail!("podman volume export failed for {}", volume);
+    }
+
+    let meta = SnapshotMeta {
+        id,
+        volume: volume.to_string(),
+        workspace: workspace.to_path_buf(),
+        reason,
+        created_at: now,
+        size: std::fs::metadata(&archive)?.len(),
+    };
+    std::fs::write(
+        archive.with_extension("json"),
+        serde_json::to_string_pretty(&meta)?,
+    )
+    .context("Failed to write snapshot metadata")?;
+
+    println!(
+        "{} {} ({}, {})",
+        "Snapshot saved:".green(),
+        meta.id,
+        reason.as_str(),
+        format_size(meta.size)
+    );
+
+    if reason.is_automatic() {
+        for id in prune_automatic(&dir, AUTO_SNAPSHOTS_KEPT, protected)? {
+            println!("{} {}", "Pruned old snapshot:".dimmed(), id.dimmed());
+        }
+    }
+
+    Ok(Snapshot { meta, archive })
+}
+
+/// Snapshots `volume` before a destructive operation if it exists.
+pub fn auto_snapshot(
+    config: &AppConfig,
+    workspace: &Path,
+    volume: &str,
+    reason: SnapshotReason,
+) -> Result<Option<Snapshot>> {
+    if !container::volume_exists(volume)? {
+        return Ok(None);
+    }
+    snapshot(config, workspace, volume, reason).map(Some)
+}
+
+/// Snapshots `volume` before its contents are replaced with `source`, which may itself
+/// be a snapshot archive and so must survive the pruning.
+fn snapshot_before_replacing(
+    config: &AppConfig,
+    workspace: &Path,
+    volume: &str,
+    reason: SnapshotReason,
+    source: &Path,
+) -> Result<()> {
+    if container::volume_exists(volume)? {
+        take_snapshot(config, workspace, volume, reason, Some(source))?;
+    }
+    Ok(())
+}
+
+pub fn print_snapshots(config: &AppConfig, volume: &str) -> Result<()> {
+    let snapshots = read_snapshots(&volume_dir(config, volume))?;
+    if snapshots.is_empty() {
+        println!("{} {}", "No snapshots for".yellow(), volume);
+        return Ok(());
+    }
+
+    println!("{} {}", "Snapshots of".blue().bold(), volume);
+    println!("{:<22} {:<10} {:<12} CREATED", "ID", "REASON", "SIZE");
+    for s in &snapshots {
+        println!(
+            "{:<22} {:<10} {:<12} {}",
+            s.meta.id,
+            s.meta.reason.as_str(),
+            format_size(s.meta.size),
+            s.meta
+                .created_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    Ok(())
+}
+
+/// Restores `volume` from a snapshot id (or `latest`), snapshotting the current
+/// contents first.
+pub fn restore(config: &AppConfig, workspace: &Path, volume: &str, reference: &str) -> Result<()> {
+    let snapshots = read_snapshots(&volume_dir(config, volume))?;
+    let snapshot = find_snapshot(snapshots, reference).with_context(|| {
+        format!(
+            "No snapshot '{}' for {} (see `ai-pod volume list`)",
+            reference, volume
+        )
+    })?;
+
+    ensure_volume_unused(volume)?;
+    snapshot_before_replacing(
+        config,
+        workspace,
+        volume,
+        SnapshotReason::Restore,
+        &snapshot.archive,
+    )?;
+
+    println!(
+        "{} {} from {}",
+        "Restoring volume:".blue().bold(),
+        volume,
+        snapshot.meta.id
+    );
+    replace_volume_contents(volume, &snapshot.archive)?;
+    println!("{}", "Volume restored.".green());
+    Ok(())
+}
+
+/// Writes the contents of `volume` to a tar archive.
+pub fn export(volume: &str, path: &Path) -> Result<()> {
+    ensure_volume_exists(volume)?;
+    let status = Command::new("podman")
+        .args(["volume", "export", "--output"])
+        .arg(path)
+        .arg(volume)
+        .status()
+        .context("Failed to run podman volume export")?;
+    if !status.success() {
+        anyhow::bail!("podman volume export failed for {}", volume);
+    }
+    println!("{} {} -> {}", "Exported:".green(), volume, path.display());
+    Ok(())
+}
+
+/// Replaces the contents of `volume` with a tar archive, snapshotting the current
+/// contents first.
+pub fn import(config: &AppConfig, workspace: &Path, volume: &str, path: &Path) -> Result<()> {
+    if !path.is_file() {
+        anyhow::bail!("{} is not a file", path.display());
+    }
+    ensure_volume_unused(volume)?;
+    snapshot_before_replacing(config, workspace, volume, SnapshotReason::Import, path)?;
+
+    println!(
+        "{} {} from {}",
+        "Importing volume:".blue().bold(),
+        volume,
+        path.display()
+    );
+    replace_volume_contents(volume, path)?;
+    println!("{}", "Volume imported.".green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn write_snapshot(dir: &Path, id: &str, reason: SnapshotReason, minute: u32) {
+        let meta = SnapshotMeta {
+            id: id.to_string(),
+            volume: "claude-abc-home".into(),
+            workspace: "/w".into(),
+            reason,
+            created_at: Utc.with_ymd_and_hms(2026, 1, 1, 12, minute, 0).unwrap(),
+            size: 10,
+        };
+        std::fs::write(dir.join(format!("{}.tar", id)), "tar").unwrap();
+        std::fs::write(
+            dir.join(format!("{}.json", id)),
+            serde_json::to_string(&meta).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn snapshot_ids_are_unique_within_a_second() {
+        let dir = TempDir::new().unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        let first = new_snapshot_id(dir.path(), now);
+        assert_eq!(first, "20260101T120000Z");
+        std::fs::write(dir.path().join(format!("{}.tar", first)), "").unwrap();
+        assert_eq!(new_snapshot_id(dir.path(), now), "20260101T120000Z-2");
+    }
+
+    #[test]
+    fn read_snapshots_sorts_and_skips_incomplete_entries() {
+        let dir = TempDir::new().unwrap();
+        write_snapshot(dir.path(), "b", SnapshotReason::Manual, 2);
+        write_snapshot(dir.path(), "a", SnapshotReason::Clean, 1);
+        std::fs::remove_file(dir.path().join("a.tar")).unwrap();
+        write_snapshot(dir.path(), "c", SnapshotReason::Rebuild, 0);
+        std::fs::write(dir.path().join("junk.json"), "not json").unwrap();
+
+        let ids: Vec<String> = read_snapshots(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.meta.id)
+            .collect();
+        assert_eq!(ids, vec!["c", "b"]);
+    }
+
+    #[test]
+    fn prune_keeps_manual_and_newest_automatic_snapshots() {
+        let dir = TempDir::new().unwrap();
+        write_snapshot(dir.path(), "manual", SnapshotReason::Manual, 0);
+        for minute in 1..=4 {
+            write_snapshot(
+                dir.path(),
+                &format!("auto{}", minute),
+                SnapshotReason::Clean,
+                minute,
+            );
+        }
+
+        let removed = prune_automatic(dir.path(), 2, None).unwrap();
+        assert_eq!(removed, vec!["auto1", "auto2"]);
+        assert!(!dir.path().join("auto1.tar").exists());
+        assert!(!dir.path().join("auto1.json").exists());
+        assert!(dir.path().join("manual.tar").exists());
+        assert!(dir.path().join("auto4.tar").exists());
+    }
+
+    #[test]
+    fn restoring_the_oldest_automatic_snapshot_keeps_it() {
+        let dir = TempDir::new().unwrap();
+        for minute in 1..=5 {
+            write_snapshot(
+                dir.path(),
+                &format!("auto{}", minute),
+                SnapshotReason::Rebuild,
+                minute,
+            );
+        }
+        let target = find_snapshot(read_snapshots(dir.path()).unwrap(), "auto1").unwrap();
+
+        // The snapshot taken before restoring makes six automatic ones.
+        write_snapshot(dir.path(), "before", SnapshotReason::Restore, 6);
+        let removed =
+            prune_automatic(dir.path(), AUTO_SNAPSHOTS_KEPT, Some(&target.archive)).unwrap();
+        assert!(removed.is_empty());
+        assert!(target.archive.exists());
+
+        // Once restored, it ages out like the others.
+        let removed = prune_automatic(dir.path(), AUTO_SNAPSHOTS_KEPT, None).unwrap();
+        assert_eq!(removed, vec!["auto1"]);
+    }
+
+    #[test]
+    fn find_snapshot_by_id_or_latest() {
+        let dir = TempDir::new().unwrap();
+        write_snapshot(dir.path(), "old", SnapshotReason::Manual, 0);
+        write_snapshot(dir.path(), "new", SnapshotReason::Clean, 1);
+
+        let snapshots = || read_snapshots(dir.path()).unwrap();
+        assert_eq!(find_snapshot(snapshots(), "latest").unwrap().meta.id, "new");
+        assert_eq!(find_snapshot(snapshots(), "old").unwrap().meta.id, "old");
+        assert!(find_snapshot(snapshots(), "missing").is_none());
+    }
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}